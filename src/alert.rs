@@ -0,0 +1,579 @@
+//! `alert` subcommand: per-group limits checked on every sample
+//!
+//! A rule has the form `GROUP FIGURE (>|<) LIMIT [for DURATION] [clear VALUE]`, e.g.
+//! `postgres memory > 8GiB for 60s clear 7GiB`. `GROUP` is a group name or `*` for every
+//! group, and `FIGURE` one of the --sort keys except `name`. A missing group counts as 0, so
+//! `sshd count < 1` alerts when sshd is gone.
+//!
+//! A rule alerts once its limit has been crossed for `DURATION` (default: at once) and
+//! recovers once the value has been back past `VALUE` (default: the limit) for `DURATION`.
+//! With `*`, a group that disappears while alerting is reported as gone instead, and forgotten.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::process::{Child, Command};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::ArgEnum;
+use top_group::{Sampler, ScanOptions};
+
+use crate::filter::parse_size;
+use crate::time::{format_timestamp, now, parse_duration};
+use crate::{SortKey, Units};
+
+/// Direction in which a limit is crossed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Above,
+    Below,
+}
+
+/// A limit on a figure of one group, or of each group
+#[derive(Debug, Clone)]
+pub struct Rule {
+    /// `None` for every group
+    group: Option<OsString>,
+    figure: SortKey,
+    comparison: Comparison,
+    limit: u64,
+
+    /// How long the limit must be crossed before alerting, and back before recovering
+    duration: Duration,
+
+    /// Value the figure must be back past to recover
+    clear: u64,
+
+    /// The rule as given
+    text: String,
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| {
+            format!(
+                "Invalid rule {:?}: {}, expected e.g. \"postgres memory > 8GiB for 60s\"",
+                s, reason
+            )
+        };
+        let words: Vec<&str> = s.split_whitespace().collect();
+        if words.len() < 4 {
+            return Err(invalid("too few words"));
+        }
+        let group = match words[0] {
+            "*" => None,
+            name => Some(OsString::from(name)),
+        };
+        let figure = match SortKey::from_str(words[1], true) {
+            Ok(SortKey::Name) | Err(_) => {
+                return Err(invalid(&format!("unknown figure {:?}", words[1])))
+            }
+            Ok(figure) => figure,
+        };
+        let comparison = match words[2] {
+            ">" => Comparison::Above,
+            "<" => Comparison::Below,
+            _ => return Err(invalid("expected > or <")),
+        };
+        let value = |word: &str| match figure {
+            SortKey::Threads | SortKey::Fds | SortKey::Count => word
+                .parse()
+                .map_err(|_| invalid(&format!("invalid number {:?}", word))),
+            _ => parse_size(word).map_err(|e| invalid(&e)),
+        };
+        let limit = value(words[3])?;
+
+        let mut rule = Rule {
+            group,
+            figure,
+            comparison,
+            limit,
+            duration: Duration::ZERO,
+            clear: limit,
+            text: words.join(" "),
+        };
+        for option in words[4..].chunks(2) {
+            match *option {
+                ["for", duration] => {
+                    let seconds = parse_duration(duration)
+                        .ok_or_else(|| invalid(&format!("invalid duration {:?}", duration)))?;
+                    rule.duration = Duration::from_secs(seconds);
+                }
+                ["clear", clear] => rule.clear = value(clear)?,
+                _ => return Err(invalid(&format!("unexpected {:?}", option.join(" ")))),
+            }
+        }
+        let clear_ok = match comparison {
+            Comparison::Above => rule.clear <= limit,
+            Comparison::Below => rule.clear >= limit,
+        };
+        if !clear_ok {
+            return Err(invalid("clear value must not be past the limit"));
+        }
+        Ok(rule)
+    }
+}
+
+impl Rule {
+    fn crossed(&self, value: u64) -> bool {
+        match self.comparison {
+            Comparison::Above => value > self.limit,
+            Comparison::Below => value < self.limit,
+        }
+    }
+
+    fn cleared(&self, value: u64) -> bool {
+        match self.comparison {
+            Comparison::Above => value <= self.clear,
+            Comparison::Below => value >= self.clear,
+        }
+    }
+
+    fn matches(&self, name: &OsStr) -> bool {
+        self.group.as_deref().is_none_or(|group| group == name)
+    }
+}
+
+/// Reads rules from a file with one rule per line, skipping empty lines and `#` comments
+pub fn read_rules(path: &Path) -> Result<Vec<Rule>, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(Rule::from_str)
+        .collect()
+}
+
+/// Where a rule stands for one group
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Within the limit, with when it was first crossed if it is crossed now
+    Normal(Option<Instant>),
+
+    /// Alerted, with when the value first got back if it is back now
+    Alerting(Option<Instant>),
+}
+
+/// Change of a rule for a group
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Event {
+    Alert,
+    Recovered,
+
+    /// The group disappeared while alerting for a `*` rule
+    Gone,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Event::Alert => "ALERT",
+            Event::Recovered => "RECOVERED",
+            Event::Gone => "GONE",
+        })
+    }
+}
+
+impl State {
+    /// Next state after seeing `value` at `now`, and the event if any
+    fn next(self, rule: &Rule, value: u64, now: Instant) -> (State, Option<Event>) {
+        match self {
+            State::Normal(since) if rule.crossed(value) => {
+                let since = since.unwrap_or(now);
+                if now.duration_since(since) >= rule.duration {
+                    (State::Alerting(None), Some(Event::Alert))
+                } else {
+                    (State::Normal(Some(since)), None)
+                }
+            }
+            State::Normal(_) => (State::Normal(None), None),
+            State::Alerting(since) if rule.cleared(value) => {
+                let since = since.unwrap_or(now);
+                if now.duration_since(since) >= rule.duration {
+                    (State::Normal(None), Some(Event::Recovered))
+                } else {
+                    (State::Alerting(Some(since)), None)
+                }
+            }
+            State::Alerting(_) => (State::Alerting(None), None),
+        }
+    }
+}
+
+/// States of the rules for the groups that are not in the default state
+#[derive(Debug, Default)]
+struct Tracker {
+    states: HashMap<(usize, OsString), State>,
+}
+
+impl Tracker {
+    /// Feeds the values of the existing groups matching rule `index` at `now`, returning the
+    /// events with the group and value they are about
+    fn update(
+        &mut self,
+        index: usize,
+        rule: &Rule,
+        values: &HashMap<OsString, u64>,
+        now: Instant,
+    ) -> Vec<(OsString, u64, Event)> {
+        let mut events = Vec::new();
+        // Existing groups, the named group even if missing, and tracked groups that vanished
+        let mut names: Vec<&OsString> = values.keys().chain(rule.group.as_ref()).collect();
+        names.extend(
+            self.states
+                .keys()
+                .filter(|(rule_index, name)| *rule_index == index && !values.contains_key(name))
+                .map(|(_, name)| name),
+        );
+        names.sort_unstable();
+        names.dedup();
+        let names: Vec<OsString> = names.into_iter().cloned().collect();
+
+        for name in names {
+            let key = (index, name);
+            let state = self
+                .states
+                .get(&key)
+                .copied()
+                .unwrap_or(State::Normal(None));
+            let value = match values.get(&key.1) {
+                Some(value) => *value,
+                // A named group that is missing counts as 0
+                None if rule.group.is_some() => 0,
+                // A vanished group of a `*` rule is forgotten
+                None => {
+                    if let State::Alerting(_) = state {
+                        events.push((key.1.clone(), 0, Event::Gone));
+                    }
+                    self.states.remove(&key);
+                    continue;
+                }
+            };
+            let (state, event) = state.next(rule, value, now);
+            if let Some(event) = event {
+                events.push((key.1.clone(), value, event));
+            }
+            if state == State::Normal(None) {
+                self.states.remove(&key);
+            } else {
+                self.states.insert(key, state);
+            }
+        }
+        events
+    }
+}
+
+/// Rules and what to do when their state changes
+pub struct Alerts {
+    pub rules: Vec<Rule>,
+    pub units: Units,
+
+    /// Shell command run for every alert and recovery
+    pub command: Option<String>,
+}
+
+impl Alerts {
+    /// Adds what the rules need to `options`
+    pub fn scan_options(&self, options: ScanOptions) -> ScanOptions {
+        ScanOptions {
+            smaps: options.smaps || self.rules.iter().any(|rule| rule.figure.needs_smaps()),
+            fds: options.fds || self.rules.iter().any(|rule| rule.figure == SortKey::Fds),
+            ..options
+        }
+    }
+
+    /// Samples every `interval` forever, printing a line and running the command whenever a
+    /// rule alerts or recovers for a group
+    pub fn run(&self, mut sampler: Sampler, interval: Duration) -> Result<(), Box<dyn Error>> {
+        let mut tracker = Tracker::default();
+        let mut children: Vec<Child> = Vec::new();
+        loop {
+            let sample = sampler.sample()?;
+            let groups = sample.processes().name_to_group();
+            let instant = Instant::now();
+            for (index, rule) in self.rules.iter().enumerate() {
+                let values: HashMap<OsString, u64> = groups
+                    .iter()
+                    .filter(|(name, _)| rule.matches(name))
+                    .map(|(name, group)| (name.clone(), rule.figure.group_value(group)))
+                    .collect();
+                for (name, value, event) in tracker.update(index, rule, &values, instant) {
+                    self.report(rule, &name, value, event, &mut children);
+                }
+            }
+            // Reap finished commands
+            children.retain_mut(|child| matches!(child.try_wait(), Ok(None)));
+            thread::sleep(interval);
+        }
+    }
+
+    fn report(
+        &self,
+        rule: &Rule,
+        name: &OsStr,
+        value: u64,
+        event: Event,
+        children: &mut Vec<Child>,
+    ) {
+        let name = name.to_string_lossy();
+        let formatted = match event {
+            Event::Gone => "-".to_owned(),
+            _ => rule.figure.format(value, self.units),
+        };
+        println!(
+            "{} {} {} {} {}: {}",
+            format_timestamp(now()),
+            event,
+            name,
+            rule.figure
+                .to_possible_value()
+                .map_or("", |value| value.get_name()),
+            formatted,
+            rule.text
+        );
+
+        if let Some(command) = &self.command {
+            let spawned = Command::new("sh")
+                .arg("-c")
+                .arg(command)
+                .env("TOP_GROUP_EVENT", event.to_string().to_lowercase())
+                .env("TOP_GROUP_GROUP", name.as_ref())
+                .env("TOP_GROUP_VALUE", value.to_string())
+                .env("TOP_GROUP_VALUE_FORMATTED", &formatted)
+                .env("TOP_GROUP_LIMIT", rule.limit.to_string())
+                .env("TOP_GROUP_RULE", &rule.text)
+                .spawn();
+            match spawned {
+                Ok(child) => children.push(child),
+                Err(e) => eprintln!("Failed to run alert command: {}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(text: &str) -> Rule {
+        text.parse().unwrap()
+    }
+
+    fn seconds(start: Instant, seconds: u64) -> Instant {
+        start + Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn parse_rules() {
+        let parsed = rule("postgres memory > 8GiB for 60s clear 7GiB");
+        assert_eq!(parsed.group.as_deref(), Some(OsStr::new("postgres")));
+        assert_eq!(parsed.figure, SortKey::Memory);
+        assert_eq!(parsed.comparison, Comparison::Above);
+        assert_eq!(parsed.limit, 8 << 30);
+        assert_eq!(parsed.duration, Duration::from_secs(60));
+        assert_eq!(parsed.clear, 7 << 30);
+
+        let parsed = rule("*   fds  <  10");
+        assert_eq!(parsed.group, None);
+        assert_eq!(parsed.figure, SortKey::Fds);
+        assert_eq!(parsed.comparison, Comparison::Below);
+        assert_eq!(parsed.limit, 10);
+        assert_eq!(parsed.duration, Duration::ZERO);
+        assert_eq!(parsed.clear, 10, "clear defaults to the limit");
+        assert_eq!(parsed.text, "* fds < 10");
+
+        let parsed = rule("sshd COUNT < 1 clear 1 for 5m");
+        assert_eq!(parsed.figure, SortKey::Count);
+        assert_eq!(parsed.duration, Duration::from_secs(300));
+    }
+
+    #[test]
+    fn reject_invalid_rules() {
+        for text in [
+            "",
+            "postgres memory >",
+            "postgres name > 1",
+            "postgres mem > 1G",
+            "postgres memory >= 1G",
+            "postgres memory > lots",
+            "postgres threads > 1.5",
+            "postgres memory > 1G for",
+            "postgres memory > 1G for 60",
+            "postgres memory > 1G during 60s",
+            "postgres memory > 1G clear 2G",
+            "sshd count < 2 clear 1",
+        ] {
+            assert!(text.parse::<Rule>().is_err(), "{:?} was accepted", text);
+        }
+    }
+
+    #[test]
+    fn clear_may_equal_limit() {
+        assert_eq!(rule("x threads > 5 clear 5").clear, 5);
+        assert_eq!(rule("x threads < 5 clear 5").clear, 5);
+        assert_eq!(rule("x threads < 5 clear 9").clear, 9);
+    }
+
+    #[test]
+    fn alert_at_once_without_duration() {
+        let rule = rule("x threads > 5");
+        let start = Instant::now();
+        let state = State::Normal(None);
+        assert_eq!(state.next(&rule, 5, start), (State::Normal(None), None));
+        assert_eq!(
+            state.next(&rule, 6, start),
+            (State::Alerting(None), Some(Event::Alert))
+        );
+        assert_eq!(
+            State::Alerting(None).next(&rule, 5, start),
+            (State::Normal(None), Some(Event::Recovered))
+        );
+    }
+
+    #[test]
+    fn alert_after_duration() {
+        let rule = rule("x threads > 5 for 60s");
+        let start = Instant::now();
+        let (state, event) = State::Normal(None).next(&rule, 6, start);
+        assert_eq!((state, event), (State::Normal(Some(start)), None));
+        let (state, event) = state.next(&rule, 7, seconds(start, 59));
+        assert_eq!((state, event), (State::Normal(Some(start)), None));
+        assert_eq!(
+            state.next(&rule, 7, seconds(start, 60)),
+            (State::Alerting(None), Some(Event::Alert))
+        );
+    }
+
+    #[test]
+    fn dip_restarts_duration() {
+        let rule = rule("x threads > 5 for 60s");
+        let start = Instant::now();
+        let (state, _) = State::Normal(None).next(&rule, 6, start);
+        let (state, _) = state.next(&rule, 5, seconds(start, 30));
+        assert_eq!(state, State::Normal(None));
+        let (state, event) = state.next(&rule, 6, seconds(start, 61));
+        assert_eq!(
+            (state, event),
+            (State::Normal(Some(seconds(start, 61))), None)
+        );
+    }
+
+    #[test]
+    fn recover_below_clear_after_duration() {
+        let rule = rule("x threads > 10 for 60s clear 8");
+        let start = Instant::now();
+        let state = State::Alerting(None);
+        // Back within the limit but not below the clear value
+        let (state, event) = state.next(&rule, 9, start);
+        assert_eq!((state, event), (State::Alerting(None), None));
+        let (state, event) = state.next(&rule, 8, seconds(start, 10));
+        assert_eq!(
+            (state, event),
+            (State::Alerting(Some(seconds(start, 10))), None)
+        );
+        // Bouncing back up restarts the recovery
+        let (state, event) = state.next(&rule, 9, seconds(start, 20));
+        assert_eq!((state, event), (State::Alerting(None), None));
+        let (state, _) = state.next(&rule, 3, seconds(start, 30));
+        let (state, event) = state.next(&rule, 3, seconds(start, 89));
+        assert_eq!(
+            (state, event),
+            (State::Alerting(Some(seconds(start, 30))), None)
+        );
+        assert_eq!(
+            state.next(&rule, 3, seconds(start, 90)),
+            (State::Normal(None), Some(Event::Recovered))
+        );
+    }
+
+    #[test]
+    fn below_rule() {
+        let rule = rule("sshd count < 1");
+        let start = Instant::now();
+        assert_eq!(
+            State::Normal(None).next(&rule, 0, start),
+            (State::Alerting(None), Some(Event::Alert))
+        );
+        assert_eq!(
+            State::Alerting(None).next(&rule, 1, start),
+            (State::Normal(None), Some(Event::Recovered))
+        );
+    }
+
+    fn values(entries: &[(&str, u64)]) -> HashMap<OsString, u64> {
+        entries
+            .iter()
+            .map(|(name, value)| (OsString::from(name), *value))
+            .collect()
+    }
+
+    #[test]
+    fn missing_named_group_counts_as_zero() {
+        let rule = rule("sshd count < 1");
+        let mut tracker = Tracker::default();
+        let start = Instant::now();
+        let events = tracker.update(0, &rule, &values(&[]), start);
+        assert_eq!(events, [(OsString::from("sshd"), 0, Event::Alert)]);
+        let events = tracker.update(0, &rule, &values(&[("sshd", 1)]), start);
+        assert_eq!(events, [(OsString::from("sshd"), 1, Event::Recovered)]);
+        assert!(tracker.states.is_empty());
+    }
+
+    #[test]
+    fn wildcard_forgets_vanished_groups() {
+        let rule = rule("* count < 2");
+        let mut tracker = Tracker::default();
+        let start = Instant::now();
+        let events = tracker.update(0, &rule, &values(&[("a", 1), ("b", 3)]), start);
+        assert_eq!(events, [(OsString::from("a"), 1, Event::Alert)]);
+        assert_eq!(tracker.states.len(), 1);
+
+        let events = tracker.update(0, &rule, &values(&[("b", 3)]), start);
+        assert_eq!(events, [(OsString::from("a"), 0, Event::Gone)]);
+        assert!(tracker.states.is_empty());
+
+        let events = tracker.update(0, &rule, &values(&[]), start);
+        assert!(events.is_empty());
+        assert!(tracker.states.is_empty());
+    }
+
+    #[test]
+    fn wildcard_forgets_pending_groups_silently() {
+        let rule = rule("* threads > 5 for 60s");
+        let mut tracker = Tracker::default();
+        let start = Instant::now();
+        assert!(tracker
+            .update(0, &rule, &values(&[("a", 6)]), start)
+            .is_empty());
+        assert_eq!(tracker.states.len(), 1);
+        assert!(tracker
+            .update(0, &rule, &values(&[]), seconds(start, 60))
+            .is_empty());
+        assert!(tracker.states.is_empty());
+    }
+
+    #[test]
+    fn states_are_per_rule() {
+        let first = rule("* threads > 5");
+        let second = rule("* threads > 1");
+        let mut tracker = Tracker::default();
+        let start = Instant::now();
+        let groups = values(&[("a", 3)]);
+        assert!(tracker.update(0, &first, &groups, start).is_empty());
+        assert_eq!(
+            tracker.update(1, &second, &groups, start),
+            [(OsString::from("a"), 3, Event::Alert)]
+        );
+        assert!(tracker.update(0, &first, &values(&[]), start).is_empty());
+        assert_eq!(tracker.states.len(), 1, "rule 1 still alerting for a");
+    }
+}
@@ -0,0 +1,61 @@
+//! Byte quantities
+
+use std::iter::Sum;
+use std::ops::Add;
+
+/// A size in bytes
+///
+/// `/proc` reports sizes as `kB`, meaning KiB. They are converted with [`Bytes::from_kib`] as
+/// soon as they are read, so sizes returned by this crate never need to be scaled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Bytes(u64);
+
+impl Bytes {
+    /// A size of `bytes` bytes
+    pub const fn new(bytes: u64) -> Self {
+        Bytes(bytes)
+    }
+
+    /// A size given in KiB, as in the `kB` fields of `/proc`
+    pub const fn from_kib(kib: u64) -> Self {
+        Bytes(kib * 1024)
+    }
+
+    /// Number of bytes
+    pub const fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    /// `self - other`, or zero if `other` is larger
+    pub fn saturating_sub(self, other: Bytes) -> Bytes {
+        Bytes(self.0.saturating_sub(other.0))
+    }
+}
+
+impl From<Bytes> for u64 {
+    fn from(bytes: Bytes) -> u64 {
+        bytes.0
+    }
+}
+
+impl Add for Bytes {
+    type Output = Bytes;
+
+    fn add(self, other: Bytes) -> Bytes {
+        Bytes(self.0 + other.0)
+    }
+}
+
+impl Sum for Bytes {
+    fn sum<I>(iter: I) -> Self
+    where
+        I: Iterator<Item = Self>,
+    {
+        iter.fold(Bytes::default(), |acc, x| acc + x)
+    }
+}
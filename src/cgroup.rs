@@ -0,0 +1,62 @@
+//! Interpretation of `/proc/[pid]/cgroup` paths
+
+use procfs::ProcessCgroup;
+
+/// Name systemd uses for the root slice
+const ROOT_SLICE: &str = "-.slice";
+
+/// Picks the cgroup v2 entry, falling back to the systemd v1 hierarchy
+pub(crate) fn preferred_cgroup(cgroups: &[ProcessCgroup]) -> Option<&ProcessCgroup> {
+    cgroups
+        .iter()
+        .find(|cgroup| cgroup.hierarchy == 0)
+        .or_else(|| {
+            cgroups
+                .iter()
+                .find(|cgroup| cgroup.controllers.iter().any(|c| c == "name=systemd"))
+        })
+        .or_else(|| cgroups.first())
+}
+
+fn is_unit(component: &str) -> bool {
+    component.ends_with(".service") || component.ends_with(".scope")
+}
+
+/// Systemd unit of a cgroup path, as seen by the system manager
+///
+/// This is the outermost `.service` or `.scope` component, so processes managed by a user's
+/// systemd instance are attributed to `user@UID.service`.
+///
+/// ```
+/// use top_group::systemd_unit;
+///
+/// assert_eq!(systemd_unit("/system.slice/sshd.service"), Some("sshd.service"));
+/// assert_eq!(
+///     systemd_unit("/user.slice/user-1000.slice/user@1000.service/app.slice/foo.service"),
+///     Some("user@1000.service")
+/// );
+/// assert_eq!(systemd_unit("/"), None);
+/// ```
+pub fn systemd_unit(path: &str) -> Option<&str> {
+    path.split('/').find(|component| is_unit(component))
+}
+
+/// Systemd slice of a cgroup path
+///
+/// This is the innermost `.slice` component that contains the unit returned by
+/// [`systemd_unit`], or `-.slice` for units directly under the root.
+///
+/// ```
+/// use top_group::systemd_slice;
+///
+/// assert_eq!(systemd_slice("/system.slice/sshd.service"), "system.slice");
+/// assert_eq!(systemd_slice("/user.slice/user-1000.slice/session-3.scope"), "user-1000.slice");
+/// assert_eq!(systemd_slice("/init.scope"), "-.slice");
+/// ```
+pub fn systemd_slice(path: &str) -> &str {
+    path.split('/')
+        .take_while(|component| !is_unit(component))
+        .filter(|component| component.ends_with(".slice"))
+        .last()
+        .unwrap_or(ROOT_SLICE)
+}
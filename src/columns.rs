@@ -0,0 +1,183 @@
+//! Columns selectable with `--columns`
+
+use std::ffi::OsStr;
+
+use clap::ArgEnum;
+use top_group::{Bytes, MemoryUsage, ProcessGroups, ResourceCounts};
+
+use crate::Units;
+
+/// Column of the text, CSV and TSV output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum Column {
+    /// Group name
+    Name,
+    /// PID, empty in group rows
+    Pid,
+    /// Number of processes, 1 in PID rows
+    Processes,
+    /// Resident minus shared
+    Memory,
+    /// Resident set size
+    Resident,
+    /// Shared memory
+    Shared,
+    /// Swapped out memory
+    Swap,
+    /// Proportional set size
+    Pss,
+    /// Unique set size
+    Uss,
+    /// Proportional swap size
+    SwapPss,
+    /// Number of threads
+    Threads,
+    /// Number of open file descriptors
+    Fds,
+}
+
+/// Figures of a group or a single process
+pub struct Row<'a> {
+    name: &'a OsStr,
+    pid: Option<i32>,
+    processes: usize,
+    usage: MemoryUsage,
+    counts: Option<ResourceCounts>,
+}
+
+impl<'a> Row<'a> {
+    /// Totals of a group
+    pub fn group(name: &'a OsStr, group: &ProcessGroups) -> Self {
+        Row {
+            name,
+            pid: None,
+            processes: group.pid_to_usage().len(),
+            usage: group.usage_totals(),
+            counts: Some(group.counts_totals()),
+        }
+    }
+
+    /// A single process of a group, if it is in the group
+    pub fn process(name: &'a OsStr, group: &ProcessGroups, pid: i32) -> Option<Self> {
+        Some(Row {
+            name,
+            pid: Some(pid),
+            processes: 1,
+            usage: *group.pid_to_usage().get(&pid)?,
+            counts: group.pid_to_counts().get(&pid).copied(),
+        })
+    }
+}
+
+impl Column {
+    /// Columns shown when none are given
+    pub fn defaults(per_pid: bool) -> Vec<Column> {
+        let mut columns = vec![Column::Name];
+        columns.push(if per_pid {
+            Column::Pid
+        } else {
+            Column::Processes
+        });
+        columns.extend([
+            Column::Memory,
+            Column::Resident,
+            Column::Shared,
+            Column::Swap,
+        ]);
+        columns
+    }
+
+    /// Name of the column in the CSV and TSV header
+    pub fn header(self) -> &'static str {
+        match self {
+            Column::Name => "name",
+            Column::Pid => "pid",
+            Column::Processes => "processes",
+            Column::Memory => "memory_bytes",
+            Column::Resident => "resident_bytes",
+            Column::Shared => "shared_bytes",
+            Column::Swap => "swap_bytes",
+            Column::Pss => "pss_bytes",
+            Column::Uss => "uss_bytes",
+            Column::SwapPss => "swap_pss_bytes",
+            Column::Threads => "threads",
+            Column::Fds => "fds",
+        }
+    }
+
+    /// Name of the column in the text header
+    pub fn title(self) -> &'static str {
+        match self {
+            Column::Name => "NAME",
+            Column::Pid => "PID",
+            Column::Processes => "PROCS",
+            Column::Memory => "MEMORY",
+            Column::Resident => "RESIDENT",
+            Column::Shared => "SHARED",
+            Column::Swap => "SWAP",
+            Column::Pss => "PSS",
+            Column::Uss => "USS",
+            Column::SwapPss => "SWAP PSS",
+            Column::Threads => "THREADS",
+            Column::Fds => "FDS",
+        }
+    }
+
+    /// Whether the column is read from smaps_rollup
+    pub fn needs_smaps(self) -> bool {
+        matches!(self, Column::Pss | Column::Uss | Column::SwapPss)
+    }
+
+    /// Size in bytes or count of a numeric column, `None` for the name or if it was not
+    /// collected
+    fn value(self, row: &Row) -> Option<u64> {
+        match self {
+            Column::Name => None,
+            Column::Pid => row.pid.map(|pid| pid as u64),
+            Column::Processes => Some(row.processes as u64),
+            Column::Memory => Some(row.usage.memory.as_u64()),
+            Column::Resident => Some(row.usage.resident.as_u64()),
+            Column::Shared => Some(row.usage.shared.as_u64()),
+            Column::Swap => Some(row.usage.swap.as_u64()),
+            Column::Pss => row.usage.pss.map(Bytes::as_u64),
+            Column::Uss => row.usage.uss.map(Bytes::as_u64),
+            Column::SwapPss => row.usage.swap_pss.map(Bytes::as_u64),
+            Column::Threads => row.counts.map(|counts| counts.threads),
+            Column::Fds => row.counts.and_then(|counts| counts.fds),
+        }
+    }
+
+    fn is_size(self) -> bool {
+        matches!(
+            self,
+            Column::Memory
+                | Column::Resident
+                | Column::Shared
+                | Column::Swap
+                | Column::Pss
+                | Column::Uss
+                | Column::SwapPss
+        )
+    }
+
+    /// Cell for CSV and TSV: sizes as integers in bytes, empty if not collected
+    pub fn raw(self, row: &Row) -> String {
+        if self == Column::Name {
+            return row.name.to_string_lossy().into_owned();
+        }
+        self.value(row)
+            .map_or_else(String::new, |value| value.to_string())
+    }
+
+    /// Cell for text output: sizes in `units`, `-` if not collected
+    pub fn formatted(self, row: &Row, units: Units) -> String {
+        if self == Column::Name {
+            return row.name.to_string_lossy().into_owned();
+        }
+        match self.value(row) {
+            Some(bytes) if self.is_size() => units.format(Bytes::new(bytes)),
+            Some(value) => value.to_string(),
+            None => "-".to_owned(),
+        }
+    }
+}
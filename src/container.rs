@@ -0,0 +1,377 @@
+//! Detection of containers and Kubernetes pods from cgroup paths
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde_json::Value;
+
+/// Default directory holding Docker's per-container metadata
+pub const DEFAULT_DOCKER_ROOT: &str = "/var/lib/docker/containers";
+
+/// Default directory holding Podman's (containers/storage) container metadata
+pub const DEFAULT_PODMAN_ROOT: &str = "/var/lib/containers/storage/overlay-containers";
+
+/// Length of the abbreviated container IDs shown by `docker ps`
+const SHORT_ID_LEN: usize = 12;
+
+/// Container runtime that created a container
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Runtime {
+    /// Docker, from `docker-<id>.scope` or `/docker/<id>`
+    Docker,
+
+    /// Podman, from `libpod-<id>.scope`
+    Podman,
+
+    /// containerd's CRI plugin, from `cri-containerd-<id>.scope`
+    Containerd,
+
+    /// CRI-O, from `crio-<id>.scope`
+    CriO,
+
+    /// Container ID found without a recognizable runtime prefix
+    Unknown,
+}
+
+/// Container a process runs in, as derived from its cgroup path
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Container {
+    /// Runtime that created the container
+    pub runtime: Runtime,
+
+    /// Full (64 hex digit) container ID
+    pub id: String,
+
+    /// UID of the Kubernetes pod containing the container, if any
+    pub pod_uid: Option<String>,
+}
+
+impl Container {
+    /// Parses a cgroup path such as `/system.slice/docker-<id>.scope` or
+    /// `/kubepods/burstable/pod<uid>/<id>`
+    ///
+    /// If containers are nested, the innermost one is returned.
+    ///
+    /// ```
+    /// use top_group::{Container, Runtime};
+    ///
+    /// let id = "8f7c6a1e0d5b4c3a2918f7e6d5c4b3a2918f7e6d5c4b3a2918f7e6d5c4b3a291";
+    /// let path = format!(
+    ///     "/kubepods.slice/kubepods-burstable.slice/\
+    ///      kubepods-burstable-pod1b2c3d4e_0000_1111_2222_333344445555.slice/\
+    ///      cri-containerd-{}.scope",
+    ///     id
+    /// );
+    /// let container = Container::from_cgroup_path(&path).unwrap();
+    /// assert_eq!(container.runtime, Runtime::Containerd);
+    /// assert_eq!(container.id, id);
+    /// assert_eq!(
+    ///     container.pod_uid.as_deref(),
+    ///     Some("1b2c3d4e-0000-1111-2222-333344445555")
+    /// );
+    /// ```
+    pub fn from_cgroup_path(path: &str) -> Option<Self> {
+        let mut container = None;
+        let mut pod_uid = None;
+        let mut parent = "";
+        for component in path.split('/') {
+            if let Some(uid) = parse_pod_uid(component) {
+                pod_uid = Some(uid);
+            } else if let Some((runtime, id)) = parse_container_id(parent, component) {
+                container = Some((runtime, id));
+            }
+            parent = component;
+        }
+        let (runtime, id) = container?;
+        Some(Container {
+            runtime,
+            id: id.to_owned(),
+            pod_uid,
+        })
+    }
+
+    /// Abbreviated container ID
+    pub fn short_id(&self) -> &str {
+        self.id.get(..SHORT_ID_LEN).unwrap_or(&self.id)
+    }
+}
+
+fn is_container_id(id: &str) -> bool {
+    id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn parse_container_id<'a>(parent: &str, component: &'a str) -> Option<(Runtime, &'a str)> {
+    const PREFIXES: &[(&str, Runtime)] = &[
+        ("docker-", Runtime::Docker),
+        ("libpod-", Runtime::Podman),
+        ("cri-containerd-", Runtime::Containerd),
+        ("crio-", Runtime::CriO),
+    ];
+
+    let name = component.strip_suffix(".scope").unwrap_or(component);
+    for (prefix, runtime) in PREFIXES {
+        if let Some(id) = name.strip_prefix(prefix) {
+            // Monitor processes like `libpod-conmon-<id>` don't belong to the container
+            return if is_container_id(id) {
+                Some((*runtime, id))
+            } else {
+                None
+            };
+        }
+    }
+    if is_container_id(name) {
+        let runtime = if parent == "docker" {
+            Runtime::Docker
+        } else {
+            Runtime::Unknown
+        };
+        return Some((runtime, name));
+    }
+    None
+}
+
+/// Parses `pod<uid>` (cgroupfs driver) or `kubepods-<qos>-pod<uid>.slice` (systemd driver)
+fn parse_pod_uid(component: &str) -> Option<String> {
+    let name = component.strip_suffix(".slice").unwrap_or(component);
+    let start = name.rfind("pod")?;
+    if start != 0 && !name[..start].ends_with('-') {
+        return None;
+    }
+    let uid = name[start + "pod".len()..].replace('_', "-");
+    let is_uid = uid.len() == 36
+        && uid.bytes().enumerate().all(|(i, b)| match i {
+            8 | 13 | 18 | 23 => b == b'-',
+            _ => b.is_ascii_hexdigit(),
+        });
+    if is_uid {
+        Some(uid)
+    } else {
+        None
+    }
+}
+
+/// Mapping from container ID to human readable container name
+///
+/// Names are read from the runtimes' local metadata directories.
+#[derive(Debug, Clone, Default)]
+pub struct ContainerNames {
+    id_to_name: HashMap<String, String>,
+}
+
+impl ContainerNames {
+    /// Creates an empty mapping
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Reads names from Docker's `<root>/<id>/config.v2.json` files
+    ///
+    /// Containers whose config cannot be read or parsed, e.g. because Docker is just writing
+    /// it, are skipped. Only failing to list `root` is an error.
+    pub fn read_docker(&mut self, root: impl AsRef<Path>) -> io::Result<()> {
+        for entry in fs::read_dir(root)? {
+            let config_path = match entry {
+                Ok(entry) => entry.path().join("config.v2.json"),
+                Err(_) => continue,
+            };
+            let config: Value = match fs::read(&config_path)
+                .ok()
+                .and_then(|config| serde_json::from_slice(&config).ok())
+            {
+                Some(config) => config,
+                None => continue,
+            };
+            if let (Some(id), Some(name)) = (config["ID"].as_str(), config["Name"].as_str()) {
+                let name = name.trim_start_matches('/');
+                self.id_to_name.insert(id.to_owned(), name.to_owned());
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads names from the `<root>/containers.json` file of containers/storage, as used by
+    /// Podman and CRI-O
+    pub fn read_podman(&mut self, root: impl AsRef<Path>) -> io::Result<()> {
+        let containers = fs::read(root.as_ref().join("containers.json"))?;
+        let containers: Value = serde_json::from_slice(&containers)?;
+        for container in containers.as_array().into_iter().flatten() {
+            let id = container["id"].as_str();
+            let name = container["names"][0].as_str();
+            if let (Some(id), Some(name)) = (id, name) {
+                self.id_to_name.insert(id.to_owned(), name.to_owned());
+            }
+        }
+        Ok(())
+    }
+
+    /// Name of a container
+    pub fn name(&self, id: &str) -> Option<&str> {
+        self.id_to_name.get(id).map(String::as_str)
+    }
+
+    /// Number of known container names
+    pub fn len(&self) -> usize {
+        self.id_to_name.len()
+    }
+
+    /// Whether no container names are known
+    pub fn is_empty(&self) -> bool {
+        self.id_to_name.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+    fn id(digit: char) -> String {
+        digit.to_string().repeat(64)
+    }
+
+    #[test]
+    fn read_docker_skips_bad_configs() {
+        let mut names = ContainerNames::new();
+        names.read_docker(format!("{}/docker", FIXTURES)).unwrap();
+        assert_eq!(names.name(&id('1')), Some("web"));
+        assert_eq!(names.name(&id('2')), None, "half-written config");
+        assert_eq!(names.name(&id('3')), Some("cache"));
+        assert_eq!(names.name(&id('4')), None, "no config");
+        assert_eq!(names.len(), 2);
+    }
+
+    #[test]
+    fn read_docker_missing_root() {
+        let mut names = ContainerNames::new();
+        let e = names
+            .read_docker(format!("{}/nonexistent", FIXTURES))
+            .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::NotFound);
+        assert!(names.is_empty());
+    }
+
+    #[test]
+    fn read_podman() {
+        let mut names = ContainerNames::new();
+        names.read_podman(format!("{}/podman", FIXTURES)).unwrap();
+        assert_eq!(names.name(&id('5')), Some("nginx"));
+        assert_eq!(names.name(&id('6')), Some("toolbox"));
+        assert_eq!(names.name(&id('7')), None, "no names");
+        assert_eq!(names.len(), 2);
+    }
+
+    #[test]
+    fn pod_uid_systemd_driver() {
+        assert_eq!(
+            parse_pod_uid("kubepods-burstable-pod1b2c3d4e_0000_1111_2222_333344445555.slice")
+                .as_deref(),
+            Some("1b2c3d4e-0000-1111-2222-333344445555")
+        );
+        assert_eq!(
+            parse_pod_uid("kubepods-pod1b2c3d4e_0000_1111_2222_333344445555.slice").as_deref(),
+            Some("1b2c3d4e-0000-1111-2222-333344445555")
+        );
+        assert_eq!(parse_pod_uid("kubepods-burstable.slice"), None);
+    }
+
+    #[test]
+    fn pod_uid_cgroupfs_driver() {
+        assert_eq!(
+            parse_pod_uid("pod1b2c3d4e-0000-1111-2222-333344445555").as_deref(),
+            Some("1b2c3d4e-0000-1111-2222-333344445555")
+        );
+        assert_eq!(parse_pod_uid("pod1234"), None);
+        assert_eq!(
+            parse_pod_uid("ipod1b2c3d4e-0000-1111-2222-333344445555"),
+            None
+        );
+        assert_eq!(parse_pod_uid("kubepods"), None);
+    }
+
+    #[test]
+    fn container_id_systemd_driver() {
+        let scope = |prefix: &str| format!("{}{}.scope", prefix, id('a'));
+        assert_eq!(
+            parse_container_id("system.slice", &scope("docker-")),
+            Some((Runtime::Docker, id('a').as_str()))
+        );
+        assert_eq!(
+            parse_container_id("machine.slice", &scope("libpod-")),
+            Some((Runtime::Podman, id('a').as_str()))
+        );
+        assert_eq!(
+            parse_container_id("kubepods.slice", &scope("cri-containerd-")),
+            Some((Runtime::Containerd, id('a').as_str()))
+        );
+        assert_eq!(
+            parse_container_id("kubepods.slice", &scope("crio-")),
+            Some((Runtime::CriO, id('a').as_str()))
+        );
+        assert_eq!(
+            parse_container_id("machine.slice", &scope("libpod-conmon-")),
+            None
+        );
+        assert_eq!(
+            parse_container_id("system.slice", "docker-1234.scope"),
+            None
+        );
+    }
+
+    #[test]
+    fn container_id_cgroupfs_driver() {
+        assert_eq!(
+            parse_container_id("docker", &id('b')),
+            Some((Runtime::Docker, id('b').as_str()))
+        );
+        assert_eq!(
+            parse_container_id("pod1b2c3d4e-0000-1111-2222-333344445555", &id('b')),
+            Some((Runtime::Unknown, id('b').as_str()))
+        );
+        assert_eq!(parse_container_id("docker", "init.scope"), None);
+    }
+
+    #[test]
+    fn nested_docker_path() {
+        let path = format!("/docker/{}/docker/{}", id('c'), id('d'));
+        let container = Container::from_cgroup_path(&path).unwrap();
+        assert_eq!(container.runtime, Runtime::Docker);
+        assert_eq!(container.id, id('d'));
+        assert_eq!(container.pod_uid, None);
+        assert_eq!(container.short_id(), &id('d')[..SHORT_ID_LEN]);
+    }
+
+    #[test]
+    fn conmon_is_not_a_container() {
+        let path = format!("/machine.slice/libpod-conmon-{}.scope", id('e'));
+        assert_eq!(Container::from_cgroup_path(&path), None);
+    }
+
+    #[test]
+    fn cgroupfs_pod_path() {
+        let path = format!(
+            "/kubepods/burstable/pod1b2c3d4e-0000-1111-2222-333344445555/{}",
+            id('f')
+        );
+        let container = Container::from_cgroup_path(&path).unwrap();
+        assert_eq!(container.runtime, Runtime::Unknown);
+        assert_eq!(
+            container.pod_uid.as_deref(),
+            Some("1b2c3d4e-0000-1111-2222-333344445555")
+        );
+    }
+
+    #[test]
+    fn short_id() {
+        let mut container = Container {
+            runtime: Runtime::Docker,
+            id: id('a'),
+            pod_uid: None,
+        };
+        assert_eq!(container.short_id(), "aaaaaaaaaaaa");
+        container.id = "abc".to_owned();
+        assert_eq!(container.short_id(), "abc");
+    }
+}
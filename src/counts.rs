@@ -0,0 +1,57 @@
+//! Thread and file descriptor counts
+
+use std::iter::Sum;
+use std::ops::Add;
+
+use procfs::process::Process;
+
+use crate::add_optional;
+
+/// Number of threads and open file descriptors of a process
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ResourceCounts {
+    /// Number of threads (`num_threads` in `/proc/[pid]/stat`, same as `Threads` in
+    /// `/proc/[pid]/status`)
+    pub threads: u64,
+
+    /// Number of open file descriptors (entries in `/proc/[pid]/fd`)
+    ///
+    /// Only collected with [`ScanOptions::fds`](crate::ScanOptions::fds). `None` if it was not
+    /// collected or could not be read; totals only include processes for which it was read.
+    pub fds: Option<u64>,
+}
+
+impl ResourceCounts {
+    pub(crate) fn read(proc: &Process, fds: bool) -> Self {
+        let fds = if fds {
+            proc.fd_count().ok().map(|count| count as u64)
+        } else {
+            None
+        };
+        ResourceCounts {
+            threads: proc.stat.num_threads.max(0) as u64,
+            fds,
+        }
+    }
+}
+
+impl Add for ResourceCounts {
+    type Output = ResourceCounts;
+
+    fn add(self, other: ResourceCounts) -> ResourceCounts {
+        ResourceCounts {
+            threads: self.threads + other.threads,
+            fds: add_optional(self.fds, other.fds),
+        }
+    }
+}
+
+impl Sum for ResourceCounts {
+    fn sum<I>(iter: I) -> Self
+    where
+        I: Iterator<Item = Self>,
+    {
+        iter.fold(ResourceCounts::default(), |acc, x| acc + x)
+    }
+}
@@ -0,0 +1,109 @@
+//! Accounting of which processes a scan included
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::error::Error;
+
+/// Why a process was left out of a scan
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SkipReason {
+    /// Insufficient permissions to read the process' `/proc` files
+    PermissionDenied,
+
+    /// The process exited during the scan
+    Exited,
+
+    /// The process is a zombie, so it has no memory
+    Zombie,
+
+    /// The process has no memory information, e.g. it is being torn down
+    NoMemoryInfo,
+
+    /// The group key could not be determined
+    NoKey,
+
+    /// Any other error reading the process' `/proc` files
+    Other,
+}
+
+impl SkipReason {
+    pub(crate) fn from_error(error: &Error) -> Self {
+        match error {
+            Error::PermissionDenied(_) => SkipReason::PermissionDenied,
+            Error::NotFound(_) => SkipReason::Exited,
+            _ => SkipReason::Other,
+        }
+    }
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            SkipReason::PermissionDenied => "permission denied",
+            SkipReason::Exited => "exited",
+            SkipReason::Zombie => "zombie",
+            SkipReason::NoMemoryInfo => "no memory info",
+            SkipReason::NoKey => "no group key",
+            SkipReason::Other => "other error",
+        };
+        f.write_str(reason)
+    }
+}
+
+/// How many processes a scan included, and why others were not
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct Coverage {
+    /// Number of processes included in a group
+    pub included: usize,
+
+    /// Number of kernel threads, which have no user space memory and are never grouped
+    pub kernel_threads: usize,
+
+    /// Number of skipped processes by reason
+    pub skipped: BTreeMap<SkipReason, usize>,
+}
+
+impl Coverage {
+    /// Total number of skipped processes
+    pub fn skipped_total(&self) -> usize {
+        self.skipped.values().sum()
+    }
+
+    /// Total number of processes seen, including kernel threads
+    pub fn total(&self) -> usize {
+        self.included + self.kernel_threads + self.skipped_total()
+    }
+
+    pub(crate) fn skip(&mut self, reason: SkipReason) {
+        *self.skipped.entry(reason).or_default() += 1;
+    }
+}
+
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} processes included, {} kernel threads, {} skipped",
+            self.included,
+            self.total(),
+            self.kernel_threads,
+            self.skipped_total()
+        )?;
+        if !self.skipped.is_empty() {
+            let reasons: Vec<String> = self
+                .skipped
+                .iter()
+                .map(|(reason, count)| format!("{}: {}", reason, count))
+                .collect();
+            write!(f, " ({})", reasons.join(", "))?;
+        }
+        Ok(())
+    }
+}
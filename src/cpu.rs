@@ -0,0 +1,62 @@
+//! CPU time accounting from `/proc/[pid]/stat`
+
+use std::iter::Sum;
+use std::ops::Add;
+
+use procfs::process::Process;
+
+/// CPU time consumed by a process since it started, in clock ticks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CpuTime {
+    /// Time spent in user mode (`utime`)
+    pub user: u64,
+
+    /// Time spent in kernel mode (`stime`)
+    pub system: u64,
+}
+
+impl CpuTime {
+    pub(crate) fn from_process(proc: &Process) -> Self {
+        CpuTime {
+            user: proc.stat.utime,
+            system: proc.stat.stime,
+        }
+    }
+
+    /// User plus system time
+    pub fn total(&self) -> u64 {
+        self.user + self.system
+    }
+
+    /// Time consumed since `earlier`, or all of `self` if the counters went backwards
+    pub fn since(&self, earlier: &CpuTime) -> CpuTime {
+        if self.user < earlier.user || self.system < earlier.system {
+            return *self;
+        }
+        CpuTime {
+            user: self.user - earlier.user,
+            system: self.system - earlier.system,
+        }
+    }
+}
+
+impl Add for CpuTime {
+    type Output = CpuTime;
+
+    fn add(self, other: CpuTime) -> CpuTime {
+        CpuTime {
+            user: self.user + other.user,
+            system: self.system + other.system,
+        }
+    }
+}
+
+impl Sum for CpuTime {
+    fn sum<I>(iter: I) -> Self
+    where
+        I: Iterator<Item = Self>,
+    {
+        iter.fold(CpuTime::default(), |acc, x| acc + x)
+    }
+}
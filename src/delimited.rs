@@ -0,0 +1,44 @@
+//! `--format csv` and `--format tsv` output
+//!
+//! The first row names the columns. Sizes are integers in bytes, and figures that were not
+//! collected (e.g. `pss` without smaps_rollup) are empty.
+
+use std::ffi::OsStr;
+use std::io;
+
+use top_group::ProcessGroups;
+
+use crate::columns::{Column, Row};
+
+/// Writes `groups`, in the given order, separated by `delimiter`
+///
+/// With `per_pid`, there is one row for each process instead of one for each group, sorted by
+/// PID within the group.
+pub fn print(
+    groups: &[(&OsStr, &ProcessGroups)],
+    columns: &[Column],
+    delimiter: u8,
+    per_pid: bool,
+) -> csv::Result<()> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(io::stdout());
+    writer.write_record(columns.iter().map(|column| column.header()))?;
+    for (name, group) in groups {
+        if per_pid {
+            let mut pids: Vec<&i32> = group.pid_to_usage().keys().collect();
+            pids.sort_unstable();
+            for row in pids
+                .into_iter()
+                .filter_map(|pid| Row::process(name, group, *pid))
+            {
+                writer.write_record(columns.iter().map(|column| column.raw(&row)))?;
+            }
+        } else {
+            let row = Row::group(name, group);
+            writer.write_record(columns.iter().map(|column| column.raw(&row)))?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
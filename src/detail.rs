@@ -0,0 +1,139 @@
+//! `--detail` and `--tree` listings of the processes in each group
+
+use std::collections::HashMap;
+
+use clap::ArgEnum;
+use top_group::{ProcessGroups, ProcessInfo, UserNames};
+
+use crate::{SortKey, Units};
+
+/// What is needed to describe processes beyond their group
+pub struct Detail {
+    pub users: UserNames,
+    pub sort: SortKey,
+    pub units: Units,
+
+    /// Whether processes are nested below their parents
+    pub tree: bool,
+
+    /// System uptime in seconds, to show how long processes have been running
+    pub uptime: f64,
+    pub ticks_per_second: u64,
+}
+
+impl Detail {
+    /// Column titles of the process lines
+    pub fn header(&self) -> String {
+        format!(
+            "  {:>7} {:>7} {:10} {} {:>11} {:>10} COMMAND",
+            "PID",
+            "PPID",
+            "USER",
+            "S",
+            "ELAPSED",
+            match self.sort {
+                SortKey::Name => "MEMORY".to_owned(),
+                sort => sort
+                    .to_possible_value()
+                    .map_or_else(String::new, |value| value.get_name().to_uppercase()),
+            }
+        )
+    }
+
+    /// Prints one line for each process of a group, sorted by PID or as a tree
+    pub fn print(&self, group: &ProcessGroups) {
+        let mut pids: Vec<i32> = group.pid_to_usage().keys().copied().collect();
+        pids.sort_unstable();
+        if !self.tree {
+            for pid in pids {
+                self.print_process(group, pid, 0);
+            }
+            return;
+        }
+
+        let info = group.pid_to_info();
+        let mut children: HashMap<i32, Vec<i32>> = HashMap::new();
+        let mut roots = Vec::new();
+        for pid in pids {
+            match info.get(&pid) {
+                Some(process) if info.contains_key(&process.ppid) && process.ppid != pid => {
+                    children.entry(process.ppid).or_default().push(pid)
+                }
+                _ => roots.push(pid),
+            }
+        }
+        let mut stack: Vec<(usize, i32)> = roots.into_iter().rev().map(|pid| (0, pid)).collect();
+        while let Some((depth, pid)) = stack.pop() {
+            self.print_process(group, pid, depth);
+            if let Some(children) = children.get(&pid) {
+                stack.extend(children.iter().rev().map(|child| (depth + 1, *child)));
+            }
+        }
+    }
+
+    fn print_process(&self, group: &ProcessGroups, pid: i32, depth: usize) {
+        let value = match self.sort.pid_value(group, pid) {
+            Some(value) => self.sort.format(value, self.units),
+            None => "-".to_owned(),
+        };
+        let info = group.pid_to_info().get(&pid);
+        let (ppid, user, state, elapsed, command) = match info {
+            Some(info) => (
+                info.ppid.to_string(),
+                self.users.name_or_uid(info.uid),
+                info.state,
+                self.elapsed(info),
+                command(info),
+            ),
+            None => (
+                "-".to_owned(),
+                "-".to_owned(),
+                '-',
+                "-".to_owned(),
+                String::new(),
+            ),
+        };
+        println!(
+            "  {:>7} {:>7} {:10} {} {:>11} {:>10} {:indent$}{}",
+            pid,
+            ppid,
+            user,
+            state,
+            elapsed,
+            value,
+            "",
+            command,
+            indent = 2 * depth
+        );
+    }
+
+    /// Time since the process started, formatted like `ps -o etime`: `[[dd-]hh:]mm:ss`
+    fn elapsed(&self, info: &ProcessInfo) -> String {
+        let started = info.start_time as f64 / self.ticks_per_second.max(1) as f64;
+        let seconds = (self.uptime - started).max(0.0) as u64;
+        let (days, hours) = (seconds / 86400, seconds / 3600 % 24);
+        let (minutes, seconds) = (seconds / 60 % 60, seconds % 60);
+        if days > 0 {
+            format!("{}-{:02}:{:02}:{:02}", days, hours, minutes, seconds)
+        } else if hours > 0 {
+            format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
+        } else {
+            format!("{:02}:{:02}", minutes, seconds)
+        }
+    }
+}
+
+/// Command line, or the command name in brackets like `ps` if there is none
+///
+/// Control characters, e.g. newlines in arguments, are replaced with `?` like `ps` does.
+pub fn command(info: &ProcessInfo) -> String {
+    let command = if info.cmdline.is_empty() {
+        format!("[{}]", info.comm)
+    } else {
+        info.cmdline.join(" ")
+    };
+    command
+        .chars()
+        .map(|c| if c.is_control() { '?' } else { c })
+        .collect()
+}
@@ -0,0 +1,424 @@
+//! `snapshot` and `diff` subcommands
+
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+
+use top_group::{
+    Bytes, GroupedProcess, MemoryUsage, ProcessGroups, ResourceCounts, ScanOptions, Snapshot,
+};
+
+use crate::filter::Filter;
+use crate::time::{format_duration, format_timestamp};
+use crate::{SortKey, Units};
+
+/// Scans processes and saves them to `path`
+pub fn save(options: &ScanOptions, path: &Path) -> Result<(), Box<dyn Error>> {
+    let processes = GroupedProcess::scan(options)?;
+    Snapshot::new(processes)
+        .save(path)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(())
+}
+
+/// Snapshots being compared and how to show the comparison
+pub struct Diff<'a> {
+    pub sort: SortKey,
+    pub units: Units,
+    pub filter: &'a Filter,
+}
+
+/// One line of the comparison, for a group or one of its processes
+struct Row {
+    /// `+` if only in the new snapshot, `-` if only in the old one
+    marker: char,
+    label: String,
+
+    /// --sort figure in each snapshot
+    old: Option<u64>,
+    new: Option<u64>,
+
+    /// Other figures that changed
+    changes: String,
+}
+
+impl Row {
+    fn growth(&self) -> i128 {
+        self.new.unwrap_or(0) as i128 - self.old.unwrap_or(0) as i128
+    }
+}
+
+/// Figures of a group or process that are compared, `None` where not collected
+struct Figures {
+    processes: Option<u64>,
+    sizes: [(&'static str, Option<u64>); 7],
+    threads: Option<u64>,
+    fds: Option<u64>,
+}
+
+impl Figures {
+    fn group(group: &ProcessGroups) -> Self {
+        let mut figures = Figures::new(&group.usage_totals(), Some(&group.counts_totals()));
+        figures.processes = Some(group.pid_to_usage().len() as u64);
+        figures
+    }
+
+    fn process(group: &ProcessGroups, pid: i32) -> Option<Self> {
+        let usage = group.pid_to_usage().get(&pid)?;
+        Some(Figures::new(usage, group.pid_to_counts().get(&pid)))
+    }
+
+    fn new(usage: &MemoryUsage, counts: Option<&ResourceCounts>) -> Self {
+        Figures {
+            processes: None,
+            sizes: [
+                ("memory", Some(usage.memory.as_u64())),
+                ("resident", Some(usage.resident.as_u64())),
+                ("shared", Some(usage.shared.as_u64())),
+                ("swap", Some(usage.swap.as_u64())),
+                ("pss", usage.pss.map(|pss| pss.as_u64())),
+                ("uss", usage.uss.map(|uss| uss.as_u64())),
+                ("swap-pss", usage.swap_pss.map(|swap_pss| swap_pss.as_u64())),
+            ],
+            threads: counts.map(|counts| counts.threads),
+            fds: counts.and_then(|counts| counts.fds),
+        }
+    }
+
+    /// Every figure that changed, e.g. `resident +1.2MiB, threads -2`
+    fn changes(&self, new: &Figures, units: Units) -> String {
+        let mut changes = Vec::new();
+        let mut compare = |label: &str, old: Option<u64>, new: Option<u64>, size: bool| {
+            if let (Some(old), Some(new)) = (old, new) {
+                if old != new {
+                    changes.push(format!("{} {}", label, signed(old, new, size, units)));
+                }
+            }
+        };
+        compare("processes", self.processes, new.processes, false);
+        for ((label, old), (_, new)) in self.sizes.iter().zip(new.sizes.iter()) {
+            compare(label, *old, *new, true);
+        }
+        compare("threads", self.threads, new.threads, false);
+        compare("fds", self.fds, new.fds, false);
+        changes.join(", ")
+    }
+}
+
+impl Diff<'_> {
+    /// Prints the changes from `old` to `new`, groups with the largest growth first
+    pub fn print(&self, old: &Snapshot, new: &Snapshot) {
+        println!("Old: {}", describe(old));
+        println!("New: {}", describe(new));
+        println!(
+            "{} apart",
+            format_duration(new.timestamp.abs_diff(old.timestamp))
+        );
+        println!();
+
+        let (rows, unchanged) =
+            self.rows(old.processes.name_to_group(), new.processes.name_to_group());
+        println!(
+            "  {:30} {:>10} {:>10} {:>11}  OTHER CHANGES",
+            "NAME", "OLD", "NEW", "CHANGE"
+        );
+        for row in &rows {
+            self.print_row(row);
+        }
+        if unchanged > 0 {
+            println!("{} groups unchanged", unchanged);
+        }
+    }
+
+    /// Rows of the groups that changed, largest growth first and each followed by the rows of
+    /// its processes, and the number of unchanged groups
+    fn rows(
+        &self,
+        old_groups: &HashMap<OsString, ProcessGroups>,
+        new_groups: &HashMap<OsString, ProcessGroups>,
+    ) -> (Vec<Row>, usize) {
+        let names: BTreeSet<&OsStr> = old_groups
+            .keys()
+            .chain(new_groups.keys())
+            .map(|name| name.as_os_str())
+            .filter(|name| {
+                let group = new_groups.get(*name).or_else(|| old_groups.get(*name));
+                group.is_some_and(|group| self.filter.matches(name, group))
+            })
+            .collect();
+
+        let mut growth: Vec<(i128, &OsStr)> = names
+            .into_iter()
+            .map(|name| {
+                let value = |groups: &HashMap<OsString, ProcessGroups>| {
+                    groups
+                        .get(name)
+                        .map_or(0, |group| self.sort.group_value(group))
+                };
+                (value(new_groups) as i128 - value(old_groups) as i128, name)
+            })
+            .collect();
+        growth.sort_unstable_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(b.1)));
+
+        let mut rows = Vec::new();
+        let mut unchanged = 0;
+        for (_, name) in growth {
+            let old_group = old_groups.get(name);
+            let new_group = new_groups.get(name);
+            let changes = match (old_group, new_group) {
+                (Some(old_group), Some(new_group)) => {
+                    Figures::group(old_group).changes(&Figures::group(new_group), self.units)
+                }
+                _ => String::new(),
+            };
+            let pids = self.pid_rows(old_group, new_group);
+            let old_value = old_group.map(|group| self.sort.group_value(group));
+            let new_value = new_group.map(|group| self.sort.group_value(group));
+            if old_value == new_value && changes.is_empty() && pids.is_empty() {
+                unchanged += 1;
+                continue;
+            }
+            let marker = match (old_group, new_group) {
+                (None, _) => '+',
+                (_, None) => '-',
+                _ => ' ',
+            };
+            rows.push(Row {
+                marker,
+                label: name.to_string_lossy().into_owned(),
+                old: old_value,
+                new: new_value,
+                changes,
+            });
+            rows.extend(pids);
+        }
+        (rows, unchanged)
+    }
+
+    /// Rows of the processes that started, exited or changed within a group
+    ///
+    /// A PID is only considered the same process if it also has the same start time.
+    fn pid_rows(&self, old: Option<&ProcessGroups>, new: Option<&ProcessGroups>) -> Vec<Row> {
+        let empty = ProcessGroups::default();
+        let (old, new) = (old.unwrap_or(&empty), new.unwrap_or(&empty));
+        let start_time =
+            |group: &ProcessGroups, pid: i32| group.pid_to_start_time().get(&pid).copied();
+        let pids: BTreeSet<i32> = old
+            .pid_to_usage()
+            .keys()
+            .chain(new.pid_to_usage().keys())
+            .copied()
+            .collect();
+
+        let mut rows = Vec::new();
+        for pid in pids {
+            let old_figures = Figures::process(old, pid);
+            let new_figures = Figures::process(new, pid);
+            let old_value = self.sort.pid_value(old, pid);
+            let new_value = self.sort.pid_value(new, pid);
+            let label = format!("  {}", pid);
+            match (old_figures, new_figures) {
+                (Some(old_figures), Some(new_figures))
+                    if start_time(old, pid) == start_time(new, pid) =>
+                {
+                    let changes = old_figures.changes(&new_figures, self.units);
+                    if !changes.is_empty() {
+                        rows.push(Row {
+                            marker: ' ',
+                            label,
+                            old: old_value,
+                            new: new_value,
+                            changes,
+                        });
+                    }
+                }
+                (old_figures, new_figures) => {
+                    if old_figures.is_some() {
+                        rows.push(Row {
+                            marker: '-',
+                            label: label.clone(),
+                            old: old_value,
+                            new: None,
+                            changes: "exited".to_owned(),
+                        });
+                    }
+                    if new_figures.is_some() {
+                        rows.push(Row {
+                            marker: '+',
+                            label,
+                            old: None,
+                            new: new_value,
+                            changes: "started".to_owned(),
+                        });
+                    }
+                }
+            }
+        }
+        rows.sort_by_key(|row| std::cmp::Reverse(row.growth()));
+        rows
+    }
+
+    fn print_row(&self, row: &Row) {
+        let format = |value: Option<u64>| {
+            value.map_or_else(
+                || "-".to_owned(),
+                |value| self.sort.format(value, self.units),
+            )
+        };
+        let change = signed(
+            row.old.unwrap_or(0),
+            row.new.unwrap_or(0),
+            !matches!(self.sort, SortKey::Threads | SortKey::Fds | SortKey::Count),
+            self.units,
+        );
+        let line = format!(
+            "{} {:30} {:>10} {:>10} {:>11}  {}",
+            row.marker,
+            row.label,
+            format(row.old),
+            format(row.new),
+            change,
+            row.changes
+        );
+        println!("{}", line.trim_end());
+    }
+}
+
+/// Signed difference, e.g. `+1.2MiB` for sizes or `-3` for counts
+fn signed(old: u64, new: u64, size: bool, units: Units) -> String {
+    let (sign, difference) = if new >= old {
+        ('+', new - old)
+    } else {
+        ('-', old - new)
+    };
+    if size {
+        format!("{}{}", sign, units.format(Bytes::new(difference)))
+    } else {
+        format!("{}{}", sign, difference)
+    }
+}
+
+/// Where and when a snapshot was taken
+fn describe(snapshot: &Snapshot) -> String {
+    format!(
+        "{} (kernel {}) at {}, {}",
+        snapshot.hostname,
+        snapshot.kernel,
+        format_timestamp(snapshot.timestamp),
+        snapshot.processes.coverage()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A group of processes given as PID, start time and resident memory in bytes
+    fn group(processes: &[(i32, u64, u64)]) -> ProcessGroups {
+        let usage = |resident: u64| json!({ "memory": resident, "resident": resident, "shared": 0, "swap": 0 });
+        let pid_to_usage: serde_json::Map<_, _> = processes
+            .iter()
+            .map(|(pid, _, resident)| (pid.to_string(), usage(*resident)))
+            .collect();
+        let pid_to_start_time: serde_json::Map<_, _> = processes
+            .iter()
+            .map(|(pid, start_time, _)| (pid.to_string(), json!(start_time)))
+            .collect();
+        let total = processes.iter().map(|(_, _, resident)| resident).sum();
+        serde_json::from_value(json!({
+            "pid_to_usage": pid_to_usage,
+            "usage_totals": usage(total),
+            "pid_to_start_time": pid_to_start_time,
+        }))
+        .unwrap()
+    }
+
+    fn groups(groups: &[(&str, ProcessGroups)]) -> HashMap<OsString, ProcessGroups> {
+        groups
+            .iter()
+            .map(|(name, group)| (OsString::from(name), group.clone()))
+            .collect()
+    }
+
+    fn rows(
+        old: &HashMap<OsString, ProcessGroups>,
+        new: &HashMap<OsString, ProcessGroups>,
+    ) -> (Vec<(char, String, String)>, usize) {
+        let filter = Filter::default();
+        let diff = Diff {
+            sort: SortKey::Resident,
+            units: Units::Raw,
+            filter: &filter,
+        };
+        let (rows, unchanged) = diff.rows(old, new);
+        let rows = rows
+            .into_iter()
+            .map(|row| (row.marker, row.label, row.changes))
+            .collect();
+        (rows, unchanged)
+    }
+
+    #[test]
+    fn added_and_removed_groups() {
+        let old = groups(&[
+            ("bash", group(&[(100, 10, 4096)])),
+            ("cron", group(&[(200, 10, 1024)])),
+        ]);
+        let new = groups(&[
+            ("bash", group(&[(100, 10, 4096)])),
+            ("sshd", group(&[(300, 50, 2048)])),
+        ]);
+        let (rows, unchanged) = rows(&old, &new);
+        assert_eq!(
+            rows,
+            [
+                ('+', "sshd".to_owned(), String::new()),
+                ('+', "  300".to_owned(), "started".to_owned()),
+                ('-', "cron".to_owned(), String::new()),
+                ('-', "  200".to_owned(), "exited".to_owned()),
+            ]
+        );
+        assert_eq!(unchanged, 1);
+    }
+
+    #[test]
+    fn reused_pid() {
+        // Same PID and memory, but started later, so a different process
+        let old = groups(&[("bash", group(&[(100, 10, 4096)]))]);
+        let new = groups(&[("bash", group(&[(100, 900, 4096)]))]);
+        let (rows, unchanged) = rows(&old, &new);
+        assert_eq!(
+            rows,
+            [
+                (' ', "bash".to_owned(), String::new()),
+                ('+', "  100".to_owned(), "started".to_owned()),
+                ('-', "  100".to_owned(), "exited".to_owned()),
+            ]
+        );
+        assert_eq!(unchanged, 0);
+    }
+
+    #[test]
+    fn same_process_changed() {
+        let old = groups(&[("bash", group(&[(100, 10, 4096)]))]);
+        let new = groups(&[("bash", group(&[(100, 10, 8192)]))]);
+        let (rows, _) = rows(&old, &new);
+        assert_eq!(
+            rows,
+            [
+                (
+                    ' ',
+                    "bash".to_owned(),
+                    "memory +4096, resident +4096".to_owned()
+                ),
+                (
+                    ' ',
+                    "  100".to_owned(),
+                    "memory +4096, resident +4096".to_owned()
+                ),
+            ]
+        );
+    }
+}
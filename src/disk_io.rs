@@ -0,0 +1,120 @@
+//! Disk I/O accounting from `/proc/[pid]/io`
+
+use std::iter::Sum;
+use std::ops::Add;
+
+use procfs::process::Process;
+
+use crate::error::Result;
+use crate::Bytes;
+
+/// I/O performed by a process since it started
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IoUsage {
+    /// Bytes fetched from the storage layer
+    pub read_bytes: Bytes,
+
+    /// Bytes sent to the storage layer
+    pub write_bytes: Bytes,
+
+    /// Number of read system calls
+    pub syscr: u64,
+
+    /// Number of write system calls
+    pub syscw: u64,
+
+    /// Bytes whose write-back was cancelled, e.g. by truncating dirty page cache
+    pub cancelled_write_bytes: Bytes,
+}
+
+impl IoUsage {
+    pub(crate) fn read(proc: &Process) -> Result<Self> {
+        let io = proc.io()?;
+        Ok(IoUsage {
+            read_bytes: Bytes::new(io.read_bytes),
+            write_bytes: Bytes::new(io.write_bytes),
+            syscr: io.syscr,
+            syscw: io.syscw,
+            cancelled_write_bytes: Bytes::new(io.cancelled_write_bytes),
+        })
+    }
+
+    /// I/O performed since `earlier`, or all of `self` if the counters went backwards
+    pub fn since(&self, earlier: &IoUsage) -> IoUsage {
+        let went_backwards = self.read_bytes < earlier.read_bytes
+            || self.write_bytes < earlier.write_bytes
+            || self.syscr < earlier.syscr
+            || self.syscw < earlier.syscw
+            || self.cancelled_write_bytes < earlier.cancelled_write_bytes;
+        if went_backwards {
+            return *self;
+        }
+        IoUsage {
+            read_bytes: self.read_bytes.saturating_sub(earlier.read_bytes),
+            write_bytes: self.write_bytes.saturating_sub(earlier.write_bytes),
+            syscr: self.syscr - earlier.syscr,
+            syscw: self.syscw - earlier.syscw,
+            cancelled_write_bytes: self
+                .cancelled_write_bytes
+                .saturating_sub(earlier.cancelled_write_bytes),
+        }
+    }
+}
+
+impl Add for IoUsage {
+    type Output = IoUsage;
+
+    fn add(self, other: IoUsage) -> IoUsage {
+        IoUsage {
+            read_bytes: self.read_bytes + other.read_bytes,
+            write_bytes: self.write_bytes + other.write_bytes,
+            syscr: self.syscr + other.syscr,
+            syscw: self.syscw + other.syscw,
+            cancelled_write_bytes: self.cancelled_write_bytes + other.cancelled_write_bytes,
+        }
+    }
+}
+
+impl Sum for IoUsage {
+    fn sum<I>(iter: I) -> Self
+    where
+        I: Iterator<Item = Self>,
+    {
+        iter.fold(IoUsage::default(), |acc, x| acc + x)
+    }
+}
+
+/// I/O per second between two samples
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct IoRates {
+    /// Bytes read from storage per second
+    pub read_bytes: f64,
+
+    /// Bytes written to storage per second
+    pub write_bytes: f64,
+
+    /// Read system calls per second
+    pub syscr: f64,
+
+    /// Write system calls per second
+    pub syscw: f64,
+
+    /// Cancelled write-back bytes per second
+    pub cancelled_write_bytes: f64,
+}
+
+impl IoRates {
+    pub(crate) fn per_second(io: &IoUsage, seconds: f64) -> Self {
+        if seconds <= 0.0 {
+            return Default::default();
+        }
+        IoRates {
+            read_bytes: io.read_bytes.as_u64() as f64 / seconds,
+            write_bytes: io.write_bytes.as_u64() as f64 / seconds,
+            syscr: io.syscr as f64 / seconds,
+            syscw: io.syscw as f64 / seconds,
+            cancelled_write_bytes: io.cancelled_write_bytes.as_u64() as f64 / seconds,
+        }
+    }
+}
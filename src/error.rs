@@ -0,0 +1,99 @@
+//! Error type for the crate
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use procfs::ProcError;
+
+/// Errors that can occur while querying processes
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// Insufficient permissions to read a file
+    PermissionDenied(Option<PathBuf>),
+
+    /// A file does not exist, usually because the process exited
+    NotFound(Option<PathBuf>),
+
+    /// A file had incomplete contents
+    Incomplete(Option<PathBuf>),
+
+    /// Any other IO error
+    Io(Arc<io::Error>, Option<PathBuf>),
+
+    /// Any other error
+    Other(String),
+}
+
+/// Result type for the crate
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::PermissionDenied(Some(path)) => {
+                write!(f, "permission denied: {}", path.display())
+            }
+            Error::PermissionDenied(None) => f.write_str("permission denied"),
+            Error::NotFound(Some(path)) => write!(f, "not found: {}", path.display()),
+            Error::NotFound(None) => f.write_str("not found"),
+            Error::Incomplete(Some(path)) => write!(f, "incomplete data: {}", path.display()),
+            Error::Incomplete(None) => f.write_str("incomplete data"),
+            Error::Io(e, Some(path)) => write!(f, "{}: {}", path.display(), e),
+            Error::Io(e, None) => write!(f, "{}", e),
+            Error::Other(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(e, _) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<ProcError> for Error {
+    fn from(error: ProcError) -> Self {
+        match error {
+            ProcError::PermissionDenied(path) => Error::PermissionDenied(path),
+            ProcError::NotFound(path) => Error::NotFound(path),
+            ProcError::Incomplete(path) => Error::Incomplete(path),
+            ProcError::Io(e, path) => Error::Io(Arc::new(e), path),
+            ProcError::Other(msg) => Error::Other(msg),
+            ProcError::InternalError(e) => Error::Other(e.to_string()),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        ProcError::from(error).into()
+    }
+}
+
+/// Error encountered while reading a single process
+#[derive(Debug, Clone)]
+pub struct ProcessError {
+    /// PID of the process
+    pub pid: i32,
+
+    /// The error
+    pub error: Error,
+}
+
+impl fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PID {}: {}", self.pid, self.error)
+    }
+}
+
+impl error::Error for ProcessError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
@@ -0,0 +1,131 @@
+//! Selection of the groups to show
+
+use std::ffi::OsStr;
+
+use clap::Args;
+use regex::Regex;
+use top_group::{Bytes, ProcessGroups};
+
+/// Which groups are shown
+#[derive(Debug, Clone, Default, Args)]
+pub struct Filter {
+    /// Only show groups whose name matches REGEX; may be repeated to show groups matching any
+    #[clap(long, value_name = "REGEX")]
+    include: Vec<Regex>,
+
+    /// Hide groups whose name matches REGEX; may be repeated
+    #[clap(long, value_name = "REGEX")]
+    exclude: Vec<Regex>,
+
+    /// Hide groups using less resident minus shared memory than SIZE, e.g. 10M or 1.5GiB
+    #[clap(long, value_name = "SIZE", parse(try_from_str = parse_size))]
+    min_memory: Option<u64>,
+
+    /// Hide groups with a smaller resident set size than SIZE
+    #[clap(long, value_name = "SIZE", parse(try_from_str = parse_size))]
+    min_resident: Option<u64>,
+
+    /// Hide groups using less shared memory than SIZE
+    #[clap(long, value_name = "SIZE", parse(try_from_str = parse_size))]
+    min_shared: Option<u64>,
+}
+
+impl Filter {
+    /// Whether a group is shown
+    pub fn matches(&self, name: &OsStr, group: &ProcessGroups) -> bool {
+        let name = name.to_string_lossy();
+        if !self.include.is_empty() && !self.include.iter().any(|re| re.is_match(&name)) {
+            return false;
+        }
+        if self.exclude.iter().any(|re| re.is_match(&name)) {
+            return false;
+        }
+        let usage = group.usage_totals();
+        let at_least = |size: Bytes, min: Option<u64>| min.is_none_or(|min| size.as_u64() >= min);
+        at_least(usage.memory, self.min_memory)
+            && at_least(usage.resident, self.min_resident)
+            && at_least(usage.shared, self.min_shared)
+    }
+}
+
+/// Parses a size in bytes with an optional unit: `K`, `M`, `G` and `T`, optionally followed by
+/// `iB`, are powers of 1024, while `kB`, `MB`, `GB` and `TB` are powers of 1000
+pub fn parse_size(size: &str) -> Result<u64, String> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid size {:?}", size))?;
+    let multiplier: u64 = match unit.trim() {
+        "" | "B" => 1,
+        "K" | "k" | "KiB" => 1 << 10,
+        "M" | "MiB" => 1 << 20,
+        "G" | "GiB" => 1 << 30,
+        "T" | "TiB" => 1 << 40,
+        "kB" | "KB" => 1_000,
+        "MB" => 1_000_000,
+        "GB" => 1_000_000_000,
+        "TB" => 1_000_000_000_000,
+        unit => return Err(format!("Unknown size unit {:?}", unit)),
+    };
+    Ok((number * multiplier as f64) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_bytes() {
+        assert_eq!(parse_size("0"), Ok(0));
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("512B"), Ok(512));
+        assert_eq!(parse_size(" 512 B "), Ok(512));
+    }
+
+    #[test]
+    fn iec_units() {
+        assert_eq!(parse_size("1K"), Ok(1024));
+        assert_eq!(parse_size("1k"), Ok(1024));
+        assert_eq!(parse_size("2KiB"), Ok(2048));
+        assert_eq!(parse_size("3M"), Ok(3 << 20));
+        assert_eq!(parse_size("3MiB"), Ok(3 << 20));
+        assert_eq!(parse_size("1.5GiB"), Ok(3 << 29));
+        assert_eq!(parse_size("1.5G"), Ok(3 << 29));
+        assert_eq!(parse_size("2TiB"), Ok(2 << 40));
+    }
+
+    #[test]
+    fn si_units() {
+        assert_eq!(parse_size("1kB"), Ok(1_000));
+        assert_eq!(parse_size("1KB"), Ok(1_000));
+        assert_eq!(parse_size("1.5MB"), Ok(1_500_000));
+        assert_eq!(parse_size("2GB"), Ok(2_000_000_000));
+        assert_eq!(parse_size("1 TB"), Ok(1_000_000_000_000));
+    }
+
+    #[test]
+    fn bad_sizes() {
+        for size in ["", "K", "-1K", "1.2.3M", "abc"] {
+            assert!(parse_size(size).is_err(), "{:?}", size);
+        }
+    }
+
+    #[test]
+    fn bad_units() {
+        for size in ["1X", "1mb", "1Kib", "1KiBs", "1 K B", "1PB"] {
+            assert_eq!(
+                parse_size(size),
+                Err(format!(
+                    "Unknown size unit {:?}",
+                    size.trim_start_matches('1').trim()
+                )),
+                "{:?}",
+                size
+            );
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! Strategies for choosing the group a process belongs to
+
+use std::ffi::OsString;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use procfs::process::Process;
+
+use crate::cgroup::{preferred_cgroup, systemd_slice, systemd_unit};
+use crate::container::{Container, ContainerNames};
+use crate::tree::TreeRollup;
+use crate::users::{UidKind, UserNames};
+
+/// Function used by [`GroupBy::Custom`] to compute a group key
+pub type GroupKeyFn = dyn Fn(&Process) -> Option<OsString> + Send + Sync;
+
+/// How processes are grouped together
+///
+/// A process whose key cannot be determined is left out of the result.
+#[derive(Clone, Default)]
+pub enum GroupBy {
+    /// Basename of the executable (`/proc/[pid]/exe`)
+    ///
+    /// Falls back to the command name if the executable cannot be read, e.g. for processes
+    /// owned by other users.
+    #[default]
+    ExeBasename,
+
+    /// Full path of the executable (`/proc/[pid]/exe`)
+    ///
+    /// Falls back to the command name if the executable cannot be read.
+    ExePath,
+
+    /// Command name (`/proc/[pid]/comm`)
+    Comm,
+
+    /// First command line argument (`argv[0]`)
+    Cmdline,
+
+    /// Owning user, from the real or effective UID in `/proc/[pid]/status`
+    ///
+    /// UIDs without an entry in `names` are reported numerically.
+    User {
+        /// Which UID to group by
+        uid: UidKind,
+
+        /// Names for UIDs
+        names: UserNames,
+    },
+
+    /// Cgroup path from `/proc/[pid]/cgroup`, preferring the cgroup v2 hierarchy
+    Cgroup,
+
+    /// Systemd unit derived from the cgroup path (see [`systemd_unit`](crate::systemd_unit))
+    SystemdUnit,
+
+    /// Systemd slice derived from the cgroup path (see [`systemd_slice`](crate::systemd_slice))
+    SystemdSlice,
+
+    /// Container the process runs in, named from `names` when known and by abbreviated ID
+    /// otherwise
+    ///
+    /// Processes outside of containers have no key.
+    Container(ContainerNames),
+
+    /// UID of the Kubernetes pod the process runs in
+    ///
+    /// Processes outside of pods have no key.
+    Pod,
+
+    /// Ancestor process, so that each process tree is reported as one group
+    ///
+    /// Groups are named after the ancestor's command name and PID, e.g. `bash [1234]`, and
+    /// carry a [`ProcessTree`](crate::ProcessTree). Since the key depends on other processes,
+    /// [`GroupBy::key`] returns `None` for this variant.
+    Ancestor(TreeRollup),
+
+    /// User supplied function
+    Custom(Arc<GroupKeyFn>),
+}
+
+impl GroupBy {
+    /// Groups by user, resolving names from a passwd(5) file
+    pub fn user(uid: UidKind, passwd: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(GroupBy::User {
+            uid,
+            names: UserNames::from_file(passwd)?,
+        })
+    }
+
+    /// Creates a [`GroupBy::Custom`] from a closure
+    pub fn custom<F>(f: F) -> Self
+    where
+        F: Fn(&Process) -> Option<OsString> + Send + Sync + 'static,
+    {
+        GroupBy::Custom(Arc::new(f))
+    }
+
+    /// Computes the group key for a process
+    ///
+    /// Returns `None` if the key cannot be determined, e.g. due to insufficient permissions.
+    pub fn key(&self, proc: &Process) -> Option<OsString> {
+        match self {
+            GroupBy::ExeBasename => match proc.exe() {
+                Ok(exe) => exe.file_name().map(ToOwned::to_owned),
+                Err(_) => Some(comm(proc)),
+            },
+            GroupBy::ExePath => match proc.exe() {
+                Ok(exe) => Some(exe.into_os_string()),
+                Err(_) => Some(comm(proc)),
+            },
+            GroupBy::Comm => Some(comm(proc)),
+            GroupBy::Cmdline => proc.cmdline().ok()?.into_iter().next().map(Into::into),
+            GroupBy::User { uid, names } => {
+                let status = proc.status().ok()?;
+                let uid = match uid {
+                    UidKind::Real => status.ruid,
+                    UidKind::Effective => status.euid,
+                };
+                Some(names.name_or_uid(uid).into())
+            }
+            GroupBy::Cgroup => cgroup_path(proc).map(Into::into),
+            GroupBy::SystemdUnit => systemd_unit(&cgroup_path(proc)?).map(Into::into),
+            GroupBy::SystemdSlice => Some(systemd_slice(&cgroup_path(proc)?).into()),
+            GroupBy::Container(names) => {
+                let container = Container::from_cgroup_path(&cgroup_path(proc)?)?;
+                let name = match names.name(&container.id) {
+                    Some(name) => name,
+                    None => container.short_id(),
+                };
+                Some(name.into())
+            }
+            GroupBy::Pod => Container::from_cgroup_path(&cgroup_path(proc)?)?
+                .pod_uid
+                .map(Into::into),
+            GroupBy::Ancestor(_) => None,
+            GroupBy::Custom(f) => f(proc),
+        }
+    }
+}
+
+impl fmt::Debug for GroupBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GroupBy::ExeBasename => f.write_str("ExeBasename"),
+            GroupBy::ExePath => f.write_str("ExePath"),
+            GroupBy::Comm => f.write_str("Comm"),
+            GroupBy::Cmdline => f.write_str("Cmdline"),
+            GroupBy::User { uid, .. } => f.debug_struct("User").field("uid", uid).finish(),
+            GroupBy::Cgroup => f.write_str("Cgroup"),
+            GroupBy::SystemdUnit => f.write_str("SystemdUnit"),
+            GroupBy::SystemdSlice => f.write_str("SystemdSlice"),
+            GroupBy::Container(names) => f.debug_tuple("Container").field(names).finish(),
+            GroupBy::Pod => f.write_str("Pod"),
+            GroupBy::Ancestor(rollup) => f.debug_tuple("Ancestor").field(rollup).finish(),
+            GroupBy::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+fn comm(proc: &Process) -> OsString {
+    proc.stat.comm.clone().into()
+}
+
+fn cgroup_path(proc: &Process) -> Option<String> {
+    let cgroups = proc.cgroups().ok()?;
+    preferred_cgroup(&cgroups).map(|cgroup| cgroup.pathname.clone())
+}
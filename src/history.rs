@@ -0,0 +1,265 @@
+//! `record` and `history` subcommands, keeping per-group samples in a SQLite database
+//!
+//! Each sample is one row per group:
+//!
+//! ```text
+//! CREATE TABLE samples (
+//!     timestamp INTEGER NOT NULL,  -- seconds since the Unix epoch
+//!     group_by TEXT NOT NULL,      -- --group-by of the recorder, e.g. exe
+//!     name TEXT NOT NULL,          -- group key, invalid UTF-8 replaced with U+FFFD
+//!     processes INTEGER NOT NULL,
+//!     memory INTEGER NOT NULL,     -- sizes in bytes
+//!     resident INTEGER NOT NULL,
+//!     shared INTEGER NOT NULL,
+//!     swap INTEGER NOT NULL,
+//!     pss INTEGER,                 -- NULL unless recorded with --smaps
+//!     uss INTEGER,
+//!     swap_pss INTEGER,
+//!     threads INTEGER NOT NULL,
+//!     fds INTEGER,                 -- NULL if no file descriptors could be counted
+//!     cpu_percent REAL             -- NULL for the first sample of a recorder
+//! )
+//! ```
+
+use std::error::Error;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use rusqlite::{params, Connection};
+use top_group::{Bytes, Sampler, ScanOptions};
+
+use crate::time::{format_timestamp, now};
+use crate::{SortKey, Units};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS samples (
+        timestamp INTEGER NOT NULL,
+        group_by TEXT NOT NULL,
+        name TEXT NOT NULL,
+        processes INTEGER NOT NULL,
+        memory INTEGER NOT NULL,
+        resident INTEGER NOT NULL,
+        shared INTEGER NOT NULL,
+        swap INTEGER NOT NULL,
+        pss INTEGER,
+        uss INTEGER,
+        swap_pss INTEGER,
+        threads INTEGER NOT NULL,
+        fds INTEGER,
+        cpu_percent REAL
+    );
+    CREATE INDEX IF NOT EXISTS samples_by_name ON samples (group_by, name, timestamp);
+    CREATE INDEX IF NOT EXISTS samples_by_time ON samples (timestamp);
+";
+
+fn open(database: &Path) -> Result<Connection, Box<dyn Error>> {
+    let connection = Connection::open(database)
+        .map_err(|e| format!("Failed to open {}: {}", database.display(), e))?;
+    // Another process, e.g. `history`, may read while the recorder writes
+    connection.busy_timeout(Duration::from_secs(5))?;
+    connection.execute_batch(SCHEMA)?;
+    Ok(connection)
+}
+
+/// Samples every `interval` forever, appending one row per group to `database`
+///
+/// Rows older than `retention` seconds are deleted after each sample.
+pub fn record(
+    options: ScanOptions,
+    group_by: &str,
+    database: &Path,
+    interval: Duration,
+    retention: Option<u64>,
+) -> Result<(), Box<dyn Error>> {
+    let mut connection = open(database)?;
+    let mut sampler = Sampler::new(options)?;
+    loop {
+        let sample = sampler.sample()?;
+        let timestamp = now();
+        let transaction = connection.transaction()?;
+        {
+            let mut insert = transaction.prepare_cached(
+                "INSERT INTO samples VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )?;
+            for (name, group) in sample.processes().name_to_group() {
+                let usage = group.usage_totals();
+                let counts = group.counts_totals();
+                insert.execute(params![
+                    timestamp,
+                    group_by,
+                    name.to_string_lossy(),
+                    group.pid_to_usage().len(),
+                    usage.memory.as_u64(),
+                    usage.resident.as_u64(),
+                    usage.shared.as_u64(),
+                    usage.swap.as_u64(),
+                    usage.pss.map(Bytes::as_u64),
+                    usage.uss.map(Bytes::as_u64),
+                    usage.swap_pss.map(Bytes::as_u64),
+                    counts.threads,
+                    counts.fds,
+                    sample
+                        .name_to_rates()
+                        .get(name)
+                        .map(|rates| rates.cpu_percent),
+                ])?;
+            }
+        }
+        if let Some(retention) = retention {
+            transaction.execute(
+                "DELETE FROM samples WHERE timestamp < ?",
+                [timestamp.saturating_sub(retention)],
+            )?;
+        }
+        transaction.commit()?;
+        thread::sleep(interval);
+    }
+}
+
+/// Recorded samples to summarize
+pub struct Query<'a> {
+    pub group_by: &'a str,
+    pub sort: SortKey,
+    pub units: Units,
+
+    /// Time range, inclusive, in seconds since the Unix epoch
+    pub since: u64,
+    pub until: u64,
+}
+
+impl Query<'_> {
+    /// Column holding the figure of `sort`
+    fn column(&self) -> &'static str {
+        match self.sort {
+            SortKey::Memory | SortKey::Name => "memory",
+            SortKey::Resident => "resident",
+            SortKey::Shared => "shared",
+            SortKey::Swap => "swap",
+            SortKey::Pss => "pss",
+            SortKey::Uss => "uss",
+            SortKey::SwapPss => "swap_pss",
+            SortKey::Threads => "threads",
+            SortKey::Fds => "fds",
+            SortKey::Count => "processes",
+        }
+    }
+
+    fn format(&self, value: Option<f64>) -> String {
+        value.map_or_else(
+            || "-".to_owned(),
+            |value| self.sort.format(value.round() as u64, self.units),
+        )
+    }
+
+    /// Prints min, average and max of `group` for each `step` seconds, or for each sample
+    /// without a step
+    pub fn print_group(
+        &self,
+        database: &Path,
+        group: &str,
+        step: Option<u64>,
+    ) -> Result<(), Box<dyn Error>> {
+        let connection = open(database)?;
+        let sql = format!(
+            "SELECT timestamp / ?1 * ?1 AS bucket, MIN({column}), AVG({column}), MAX({column}),
+                COUNT(*)
+             FROM samples
+             WHERE group_by = ?2 AND name = ?3 AND timestamp BETWEEN ?4 AND ?5
+             GROUP BY bucket ORDER BY bucket",
+            column = self.column()
+        );
+        let mut statement = connection.prepare(&sql)?;
+        let rows = statement.query_map(
+            params![
+                step.unwrap_or(1).max(1),
+                self.group_by,
+                group,
+                self.since,
+                self.until
+            ],
+            |row| {
+                Ok((
+                    row.get::<_, u64>(0)?,
+                    row.get::<_, Option<f64>>(1)?,
+                    row.get::<_, Option<f64>>(2)?,
+                    row.get::<_, Option<f64>>(3)?,
+                    row.get::<_, u64>(4)?,
+                ))
+            },
+        )?;
+
+        println!(
+            "{:23} {:>10} {:>10} {:>10} {:>7}",
+            "TIME", "MIN", "AVG", "MAX", "SAMPLES"
+        );
+        let mut any = false;
+        for row in rows {
+            let (bucket, min, avg, max, samples) = row?;
+            any = true;
+            println!(
+                "{:23} {:>10} {:>10} {:>10} {:>7}",
+                format_timestamp(bucket),
+                self.format(min),
+                self.format(avg),
+                self.format(max),
+                samples
+            );
+        }
+        if !any {
+            eprintln!(
+                "No samples of {} (grouped by {}) between {} and {}",
+                group,
+                self.group_by,
+                format_timestamp(self.since),
+                format_timestamp(self.until)
+            );
+        }
+        Ok(())
+    }
+
+    /// Prints min, average and max of every group over the whole range, the `top` groups
+    /// with the highest maximum first
+    pub fn print_groups(&self, database: &Path, top: Option<usize>) -> Result<(), Box<dyn Error>> {
+        let connection = open(database)?;
+        let sql = format!(
+            "SELECT name, MIN({column}), AVG({column}), MAX({column}), COUNT(*)
+             FROM samples
+             WHERE group_by = ?1 AND timestamp BETWEEN ?2 AND ?3
+             GROUP BY name ORDER BY MAX({column}) DESC, name
+             LIMIT ?4",
+            column = self.column()
+        );
+        let mut statement = connection.prepare(&sql)?;
+        let limit = top.map_or(-1, |top| top as i64);
+        let rows = statement.query_map(
+            params![self.group_by, self.since, self.until, limit],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<f64>>(1)?,
+                    row.get::<_, Option<f64>>(2)?,
+                    row.get::<_, Option<f64>>(3)?,
+                    row.get::<_, u64>(4)?,
+                ))
+            },
+        )?;
+
+        println!(
+            "{:30} {:>10} {:>10} {:>10} {:>7}",
+            "NAME", "MIN", "AVG", "MAX", "SAMPLES"
+        );
+        for row in rows {
+            let (name, min, avg, max, samples) = row?;
+            println!(
+                "{:30} {:>10} {:>10} {:>10} {:>7}",
+                name,
+                self.format(min),
+                self.format(avg),
+                self.format(max),
+                samples
+            );
+        }
+        Ok(())
+    }
+}
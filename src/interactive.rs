@@ -0,0 +1,487 @@
+//! Full-screen, top-like terminal mode
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::error::Error;
+use std::ffi::OsString;
+use std::io;
+use std::panic;
+use std::time::{Duration, Instant};
+
+use crossterm::cursor::Show;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use top_group::{Bytes, ProcessGroups, Sample, Sampler, ScanOptions};
+use tui::backend::{Backend, CrosstermBackend};
+use tui::layout::{Constraint, Direction, Layout};
+use tui::style::{Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Cell, Paragraph, Row, Table, TableState};
+use tui::{Frame, Terminal};
+
+use crate::Units;
+
+/// Number of rows moved by page up/down
+const PAGE: usize = 20;
+
+/// Table column, which is also a sort key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Column {
+    Name,
+    Processes,
+    Memory,
+    Resident,
+    Shared,
+    Swap,
+    Cpu,
+}
+
+impl Column {
+    const ALL: [Column; 7] = [
+        Column::Name,
+        Column::Processes,
+        Column::Memory,
+        Column::Resident,
+        Column::Shared,
+        Column::Swap,
+        Column::Cpu,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            Column::Name => "NAME",
+            Column::Processes => "PROCS",
+            Column::Memory => "MEMORY",
+            Column::Resident => "RESIDENT",
+            Column::Shared => "SHARED",
+            Column::Swap => "SWAP",
+            Column::Cpu => "CPU%",
+        }
+    }
+
+    fn width(self) -> Constraint {
+        match self {
+            Column::Name => Constraint::Min(20),
+            Column::Processes => Constraint::Length(6),
+            Column::Cpu => Constraint::Length(7),
+            _ => Constraint::Length(10),
+        }
+    }
+
+    /// Next or previous column in table order
+    fn cycle(self, forward: bool) -> Column {
+        let index = Column::ALL.iter().position(|c| *c == self).unwrap_or(0);
+        let len = Column::ALL.len();
+        let next = if forward {
+            (index + 1) % len
+        } else {
+            (index + len - 1) % len
+        };
+        Column::ALL[next]
+    }
+}
+
+/// A line of the table: either a group or one of the processes of an expanded group
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Line {
+    Group(OsString),
+    Process(OsString, i32),
+}
+
+struct App {
+    sampler: Sampler,
+    sample: Sample,
+    units: Units,
+    sort: Column,
+    descending: bool,
+    expanded: HashSet<OsString>,
+
+    /// Case-insensitive substring that group names must contain
+    filter: String,
+
+    /// Search text being typed, if the search prompt is open
+    search: Option<String>,
+
+    lines: Vec<Line>,
+    state: TableState,
+}
+
+impl App {
+    fn new(mut sampler: Sampler, units: Units) -> top_group::Result<Self> {
+        let sample = sampler.sample()?;
+        let mut app = App {
+            sampler,
+            sample,
+            units,
+            sort: Column::Memory,
+            descending: true,
+            expanded: HashSet::new(),
+            filter: String::new(),
+            search: None,
+            lines: Vec::new(),
+            state: TableState::default(),
+        };
+        app.rebuild();
+        Ok(app)
+    }
+
+    fn refresh(&mut self) -> top_group::Result<()> {
+        self.sample = self.sampler.sample()?;
+        self.rebuild();
+        Ok(())
+    }
+
+    fn group(&self, name: &OsString) -> Option<&ProcessGroups> {
+        self.sample.processes().name_to_group().get(name)
+    }
+
+    fn cpu_percent(&self, name: &OsString) -> Option<f64> {
+        self.sample
+            .name_to_rates()
+            .get(name)
+            .map(|rates| rates.cpu_percent)
+    }
+
+    fn compare(&self, a: &OsString, b: &OsString) -> Ordering {
+        let (group_a, group_b) = match (self.group(a), self.group(b)) {
+            (Some(group_a), Some(group_b)) => (group_a, group_b),
+            _ => return a.cmp(b),
+        };
+        let usage_a = group_a.usage_totals();
+        let usage_b = group_b.usage_totals();
+        let ordering = match self.sort {
+            Column::Name => a.cmp(b),
+            Column::Processes => group_a
+                .pid_to_usage()
+                .len()
+                .cmp(&group_b.pid_to_usage().len()),
+            Column::Memory => usage_a.memory.cmp(&usage_b.memory),
+            Column::Resident => usage_a.resident.cmp(&usage_b.resident),
+            Column::Shared => usage_a.shared.cmp(&usage_b.shared),
+            Column::Swap => usage_a.swap.cmp(&usage_b.swap),
+            Column::Cpu => {
+                let cpu_a = self.cpu_percent(a).unwrap_or(0.0);
+                let cpu_b = self.cpu_percent(b).unwrap_or(0.0);
+                cpu_a.partial_cmp(&cpu_b).unwrap_or(Ordering::Equal)
+            }
+        };
+        let ordering = ordering.then_with(|| a.cmp(b));
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+
+    /// Recomputes the table lines, keeping the selected line if it still exists
+    fn rebuild(&mut self) {
+        let selected = self.selected_line().cloned();
+        let filter = self.filter.to_lowercase();
+        let mut names: Vec<OsString> = self
+            .sample
+            .processes()
+            .name_to_group()
+            .keys()
+            .filter(|name| name.to_string_lossy().to_lowercase().contains(&filter))
+            .cloned()
+            .collect();
+        names.sort_by(|a, b| self.compare(a, b));
+
+        let mut lines = Vec::new();
+        for name in names {
+            if self.expanded.contains(&name) {
+                if let Some(group) = self.group(&name) {
+                    let mut pids: Vec<(i32, Bytes)> = group
+                        .pid_to_usage()
+                        .iter()
+                        .map(|(pid, usage)| (*pid, usage.memory))
+                        .collect();
+                    pids.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+                    lines.push(Line::Group(name.clone()));
+                    lines.extend(
+                        pids.into_iter()
+                            .map(|(pid, _)| Line::Process(name.clone(), pid)),
+                    );
+                    continue;
+                }
+            }
+            lines.push(Line::Group(name));
+        }
+        self.lines = lines;
+
+        let index = selected
+            .and_then(|selected| self.lines.iter().position(|line| *line == selected))
+            .or_else(|| self.state.selected())
+            .map(|index| index.min(self.lines.len().saturating_sub(1)));
+        self.state.select(if self.lines.is_empty() {
+            None
+        } else {
+            Some(index.unwrap_or(0))
+        });
+    }
+
+    fn selected_line(&self) -> Option<&Line> {
+        self.lines.get(self.state.selected()?)
+    }
+
+    fn select(&mut self, index: usize) {
+        if !self.lines.is_empty() {
+            self.state.select(Some(index.min(self.lines.len() - 1)));
+        }
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        let current = self.state.selected().unwrap_or(0) as isize;
+        self.select((current + delta).max(0) as usize);
+    }
+
+    fn set_sort(&mut self, column: Column) {
+        if self.sort == column {
+            self.descending = !self.descending;
+        } else {
+            self.sort = column;
+            self.descending = column != Column::Name;
+        }
+        self.rebuild();
+    }
+
+    fn toggle_expanded(&mut self, expand: Option<bool>) {
+        let name = match self.selected_line() {
+            Some(Line::Group(name)) | Some(Line::Process(name, _)) => name.clone(),
+            None => return,
+        };
+        let expand = expand.unwrap_or_else(|| !self.expanded.contains(&name));
+        if expand {
+            self.expanded.insert(name);
+        } else {
+            self.expanded.remove(&name);
+            // Keep the cursor on the collapsed group rather than a removed process line
+            if let Some(index) = self
+                .lines
+                .iter()
+                .position(|l| *l == Line::Group(name.clone()))
+            {
+                self.state.select(Some(index));
+            }
+        }
+        self.rebuild();
+    }
+
+    /// Handles a key press, returning `false` to quit
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if let Some(search) = &mut self.search {
+            match key.code {
+                KeyCode::Enter => self.search = None,
+                KeyCode::Esc => {
+                    self.search = None;
+                    self.filter.clear();
+                }
+                KeyCode::Backspace => {
+                    search.pop();
+                    self.filter = search.clone();
+                }
+                KeyCode::Char(c) => {
+                    search.push(c);
+                    self.filter = search.clone();
+                }
+                _ => return true,
+            }
+            self.rebuild();
+            return true;
+        }
+
+        match key.code {
+            KeyCode::Char('q') => return false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.move_by(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_by(1),
+            KeyCode::PageUp => self.move_by(-(PAGE as isize)),
+            KeyCode::PageDown => self.move_by(PAGE as isize),
+            KeyCode::Home | KeyCode::Char('g') => self.select(0),
+            KeyCode::End | KeyCode::Char('G') => self.select(usize::MAX),
+            KeyCode::Enter | KeyCode::Char(' ') => self.toggle_expanded(None),
+            KeyCode::Right | KeyCode::Char('l') => self.toggle_expanded(Some(true)),
+            KeyCode::Left | KeyCode::Char('h') => self.toggle_expanded(Some(false)),
+            KeyCode::Char('/') => self.search = Some(self.filter.clone()),
+            KeyCode::Esc => {
+                self.filter.clear();
+                self.rebuild();
+            }
+            KeyCode::Char('>') => self.set_sort(self.sort.cycle(true)),
+            KeyCode::Char('<') => self.set_sort(self.sort.cycle(false)),
+            KeyCode::Char('r') => {
+                self.descending = !self.descending;
+                self.rebuild();
+            }
+            KeyCode::Char('n') => self.set_sort(Column::Name),
+            KeyCode::Char('p') => self.set_sort(Column::Processes),
+            KeyCode::Char('m') => self.set_sort(Column::Memory),
+            KeyCode::Char('c') => self.set_sort(Column::Cpu),
+            _ => {}
+        }
+        true
+    }
+
+    fn cells(&self, line: &Line) -> Vec<String> {
+        let (name, group) = match line {
+            Line::Group(name) | Line::Process(name, _) => match self.group(name) {
+                Some(group) => (name, group),
+                None => return Vec::new(),
+            },
+        };
+        let (label, count, usage, cpu) = match line {
+            Line::Group(_) => {
+                let marker = if self.expanded.contains(name) {
+                    "-"
+                } else {
+                    "+"
+                };
+                let cpu = self
+                    .cpu_percent(name)
+                    .map_or_else(String::new, |cpu| format!("{:.1}", cpu));
+                (
+                    format!("{} {}", marker, name.to_string_lossy()),
+                    group.pid_to_usage().len().to_string(),
+                    group.usage_totals(),
+                    cpu,
+                )
+            }
+            Line::Process(_, pid) => {
+                let usage = group.pid_to_usage().get(pid).copied().unwrap_or_default();
+                (format!("    {}", pid), String::new(), usage, String::new())
+            }
+        };
+        vec![
+            label,
+            count,
+            self.units.format(usage.memory),
+            self.units.format(usage.resident),
+            self.units.format(usage.shared),
+            self.units.format(usage.swap),
+            cpu,
+        ]
+    }
+
+    fn draw<B: Backend>(&mut self, frame: &mut Frame<B>) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1),
+                Constraint::Min(1),
+                Constraint::Length(1),
+            ])
+            .split(frame.size());
+
+        let order = if self.descending { "desc" } else { "asc" };
+        let header = format!(
+            "top-group - {} groups - sorted by {} ({}) - {}",
+            self.sample.processes().name_to_group().len(),
+            self.sort.title(),
+            order,
+            self.sample.processes().coverage()
+        );
+        frame.render_widget(Paragraph::new(header), chunks[0]);
+
+        let titles = Column::ALL.iter().map(|column| {
+            let style = if *column == self.sort {
+                Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+            } else {
+                Style::default().add_modifier(Modifier::BOLD)
+            };
+            Cell::from(column.title()).style(style)
+        });
+        let rows: Vec<Row> = self
+            .lines
+            .iter()
+            .map(|line| Row::new(self.cells(line)))
+            .collect();
+        let widths: Vec<Constraint> = Column::ALL.iter().map(|c| c.width()).collect();
+        let table = Table::new(rows)
+            .header(Row::new(titles))
+            .widths(&widths)
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, chunks[1], &mut self.state);
+
+        let footer = match &self.search {
+            Some(search) => Spans::from(vec![Span::raw("/"), Span::raw(search.as_str())]),
+            None if !self.filter.is_empty() => {
+                Spans::from(format!("filter: {}  (Esc clears)  q quit", self.filter))
+            }
+            None => Spans::from(
+                "q quit  ↑↓ move  Enter expand  / search  < > sort column  r reverse  \
+                 m memory  c cpu  n name  p procs",
+            ),
+        };
+        frame.render_widget(Paragraph::new(footer), chunks[2]);
+    }
+}
+
+/// Runs the interactive mode until the user quits, rescanning every `interval`
+pub fn run(options: ScanOptions, interval: Duration, units: Units) -> Result<(), Box<dyn Error>> {
+    let mut app = App::new(Sampler::new(options)?, units)?;
+
+    let _guard = TerminalGuard::enter()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    event_loop(&mut terminal, &mut app, interval)
+}
+
+/// Raw mode and the alternate screen, left again when dropped
+///
+/// The terminal is also restored before a panic message is printed, so that the message ends
+/// up on the normal screen instead of being wiped with the alternate one.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> io::Result<Self> {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            restore_terminal();
+            hook(info);
+        }));
+        enable_raw_mode()?;
+        // From here on, dropping the guard undoes whatever part of the setup succeeded
+        let guard = TerminalGuard;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = execute!(io::stdout(), LeaveAlternateScreen, Show);
+}
+
+fn event_loop<B: Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    interval: Duration,
+) -> Result<(), Box<dyn Error>> {
+    let mut last_refresh = Instant::now();
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+
+        let timeout = interval
+            .checked_sub(last_refresh.elapsed())
+            .unwrap_or(Duration::ZERO);
+        if event::poll(timeout)? {
+            if let Event::Key(key) = event::read()? {
+                if !app.handle_key(key) {
+                    return Ok(());
+                }
+            }
+        }
+        if last_refresh.elapsed() >= interval {
+            app.refresh()?;
+            last_refresh = Instant::now();
+        }
+    }
+}
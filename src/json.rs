@@ -0,0 +1,236 @@
+//! `--format json` output
+//!
+//! The output is a single JSON object. Fields are only ever added to this schema; existing
+//! fields keep their name, type and meaning as long as `version` is 1. All sizes are in bytes.
+//!
+//! ```text
+//! {
+//!   "version": 1,
+//!   "groups": [                      // as in the text output: ordered by --sort and --order,
+//!                                    // limited by --top and the name and size filters
+//!     {
+//!       "name": "sshd",              // group key, invalid UTF-8 replaced with U+FFFD
+//!       "processes": 3,              // number of PIDs in the group
+//!       "totals": MEMORY,            // sum over all PIDs of the group
+//!       "rates": RATES | null,       // only with --cpu or --io
+//!       "pids": [                    // sorted by PID
+//!         { "pid": 812, "memory": MEMORY }
+//!       ]
+//!     }
+//!   ],
+//!   "coverage": {
+//!     "included": 412,               // processes in some group
+//!     "kernel_threads": 150,         // kernel threads, never grouped
+//!     "skipped": { "permission denied": 2 }  // processes left out, by reason
+//!   }
+//! }
+//!
+//! MEMORY = {
+//!   "memory": 1024,                  // resident minus shared
+//!   "resident": 4096,
+//!   "shared": 3072,
+//!   "swap": 0,
+//!   "pss": 2048 | null,              // null unless read from smaps_rollup (--sort pss, uss
+//!   "uss": 1024 | null,              // or swap-pss)
+//!   "swap_pss": 0 | null
+//! }
+//!
+//! RATES = {
+//!   "cpu_percent": 12.5,             // percent of one CPU
+//!   "read_bytes_per_second": 0.0,
+//!   "write_bytes_per_second": 512.0
+//! }
+//! ```
+
+use std::ffi::OsStr;
+
+use serde_json::{json, Value};
+use top_group::{Bytes, Coverage, GroupRates, MemoryUsage, ProcessGroups, Sample};
+
+/// Version of the schema above
+const VERSION: u64 = 1;
+
+/// Prints `groups`, in the given order, as a JSON document
+pub fn print(sample: &Sample, groups: &[(&OsStr, &ProcessGroups)]) {
+    let groups: Vec<Value> = groups
+        .iter()
+        .map(|(name, group)| group_json(name, group, sample.name_to_rates().get(*name)))
+        .collect();
+    let document = json!({
+        "version": VERSION,
+        "groups": groups,
+        "coverage": coverage_json(sample.processes().coverage()),
+    });
+    println!("{}", document);
+}
+
+fn group_json(name: &OsStr, group: &ProcessGroups, rates: Option<&GroupRates>) -> Value {
+    let mut pids: Vec<(&i32, &MemoryUsage)> = group.pid_to_usage().iter().collect();
+    pids.sort_unstable_by_key(|(pid, _)| **pid);
+    let pids: Vec<Value> = pids
+        .into_iter()
+        .map(|(pid, usage)| pid_json(*pid, usage))
+        .collect();
+    json!({
+        "name": name.to_string_lossy(),
+        "processes": group.pid_to_usage().len(),
+        "totals": memory_json(&group.usage_totals()),
+        "rates": rates.map(|rates| json!({
+            "cpu_percent": rates.cpu_percent,
+            "read_bytes_per_second": rates.io.read_bytes,
+            "write_bytes_per_second": rates.io.write_bytes,
+        })),
+        "pids": pids,
+    })
+}
+
+fn pid_json(pid: i32, usage: &MemoryUsage) -> Value {
+    json!({ "pid": pid, "memory": memory_json(usage) })
+}
+
+fn memory_json(usage: &MemoryUsage) -> Value {
+    json!({
+        "memory": usage.memory.as_u64(),
+        "resident": usage.resident.as_u64(),
+        "shared": usage.shared.as_u64(),
+        "swap": usage.swap.as_u64(),
+        "pss": usage.pss.map(Bytes::as_u64),
+        "uss": usage.uss.map(Bytes::as_u64),
+        "swap_pss": usage.swap_pss.map(Bytes::as_u64),
+    })
+}
+
+fn coverage_json(coverage: &Coverage) -> Value {
+    let skipped: serde_json::Map<String, Value> = coverage
+        .skipped
+        .iter()
+        .map(|(reason, count)| (reason.to_string(), json!(count)))
+        .collect();
+    json!({
+        "included": coverage.included,
+        "kernel_threads": coverage.kernel_threads,
+        "skipped": skipped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use top_group::{IoRates, SkipReason};
+
+    /// `value` with every leaf replaced by the name of its type, to compare against the schema
+    fn shape(value: &Value) -> Value {
+        match value {
+            Value::Null => json!("null"),
+            Value::Bool(_) => json!("bool"),
+            Value::Number(number) if number.is_u64() => json!("integer"),
+            Value::Number(_) => json!("float"),
+            Value::String(_) => json!("string"),
+            Value::Array(values) => values.iter().map(shape).collect(),
+            Value::Object(fields) => fields
+                .iter()
+                .map(|(name, value)| (name.clone(), shape(value)))
+                .collect(),
+        }
+    }
+
+    /// Memory usage as read from `/proc`, with `pss` given in KiB
+    fn memory(pss: Option<u64>) -> MemoryUsage {
+        let pss = pss.map(Bytes::from_kib);
+        MemoryUsage {
+            memory: Bytes::from_kib(1),
+            resident: Bytes::from_kib(4),
+            shared: Bytes::from_kib(3),
+            swap: Bytes::default(),
+            pss,
+            uss: pss,
+            swap_pss: pss,
+        }
+    }
+
+    #[test]
+    fn group_schema() {
+        let rates = GroupRates {
+            cpu_percent: 12.5,
+            io: IoRates {
+                read_bytes: 0.0,
+                write_bytes: 512.0,
+                ..Default::default()
+            },
+        };
+        let group = group_json(OsStr::new("sshd"), &ProcessGroups::default(), Some(&rates));
+        let memory = json!({
+            "memory": "integer",
+            "resident": "integer",
+            "shared": "integer",
+            "swap": "integer",
+            "pss": "null",
+            "uss": "null",
+            "swap_pss": "null",
+        });
+        assert_eq!(
+            shape(&group),
+            json!({
+                "name": "string",
+                "processes": "integer",
+                "totals": memory,
+                "rates": {
+                    "cpu_percent": "float",
+                    "read_bytes_per_second": "float",
+                    "write_bytes_per_second": "float",
+                },
+                "pids": [],
+            })
+        );
+        assert_eq!(group["name"], "sshd");
+        assert_eq!(
+            group_json(OsStr::new("sshd"), &ProcessGroups::default(), None)["rates"],
+            Value::Null
+        );
+    }
+
+    #[test]
+    fn pid_schema() {
+        let pid = pid_json(812, &memory(Some(2)));
+        assert_eq!(
+            shape(&pid),
+            json!({
+                "pid": "integer",
+                "memory": {
+                    "memory": "integer",
+                    "resident": "integer",
+                    "shared": "integer",
+                    "swap": "integer",
+                    "pss": "integer",
+                    "uss": "integer",
+                    "swap_pss": "integer",
+                },
+            })
+        );
+        // Sizes are in bytes
+        assert_eq!(pid["memory"]["resident"], 4096);
+        assert_eq!(pid["memory"]["pss"], 2048);
+        assert_eq!(
+            shape(&pid_json(812, &memory(None)))["memory"]["pss"],
+            "null"
+        );
+    }
+
+    #[test]
+    fn coverage_schema() {
+        let mut coverage = Coverage {
+            included: 412,
+            kernel_threads: 150,
+            ..Default::default()
+        };
+        coverage.skipped.insert(SkipReason::PermissionDenied, 2);
+        assert_eq!(
+            coverage_json(&coverage),
+            json!({
+                "included": 412,
+                "kernel_threads": 150,
+                "skipped": { "permission denied": 2 },
+            })
+        );
+    }
+}
@@ -5,19 +5,78 @@ use std::ffi::OsString;
 use std::iter::Sum;
 use std::ops::Add;
 
-use procfs;
+use procfs::process::{Process, StatFlags, Status};
+
+use crate::smaps::SmapsTotals;
+use crate::tree::ProcessTable;
+
+mod bytes;
+mod cgroup;
+mod container;
+mod counts;
+mod coverage;
+mod cpu;
+mod disk_io;
+mod error;
+mod group_by;
+#[cfg(feature = "serde")]
+mod os_str_keys;
+mod process_info;
+mod sample;
+mod smaps;
+#[cfg(feature = "serde")]
+mod snapshot;
+mod tree;
+mod users;
+
+pub use bytes::Bytes;
+pub use cgroup::{systemd_slice, systemd_unit};
+pub use container::{Container, ContainerNames, Runtime, DEFAULT_DOCKER_ROOT, DEFAULT_PODMAN_ROOT};
+pub use counts::ResourceCounts;
+pub use coverage::{Coverage, SkipReason};
+pub use cpu::CpuTime;
+pub use disk_io::{IoRates, IoUsage};
+pub use error::{Error, ProcessError, Result};
+pub use group_by::{GroupBy, GroupKeyFn};
+pub use process_info::ProcessInfo;
+pub use sample::{GroupRates, Sample, Sampler};
+#[cfg(feature = "serde")]
+pub use snapshot::Snapshot;
+pub use tree::{ProcessTree, TreeRollup};
+pub use users::{UidKind, UserNames, DEFAULT_PASSWD_PATH};
 
 /// Memory usage statistics
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MemoryUsage {
-    /// (resident - shared) in kB
-    pub memory: u64,
+    /// Resident minus shared
+    pub memory: Bytes,
+
+    /// Resident size
+    pub resident: Bytes,
+
+    /// Shared size
+    pub shared: Bytes,
 
-    /// Resident size in kB
-    pub resident: u64,
+    /// Swapped out anonymous memory (`VmSwap`)
+    pub swap: Bytes,
 
-    /// Shared size in kB
-    pub shared: u64,
+    /// Proportional set size: resident size with shared pages divided among the processes
+    /// sharing them
+    ///
+    /// Only collected with [`ScanOptions::smaps`]. `None` if it was not collected or could not
+    /// be read; totals only include processes for which it was read.
+    pub pss: Option<Bytes>,
+
+    /// Unique set size (private clean + private dirty): memory freed if the process exited
+    ///
+    /// Only collected with [`ScanOptions::smaps`], see [`pss`](Self::pss).
+    pub uss: Option<Bytes>,
+
+    /// Proportional swap size
+    ///
+    /// Only collected with [`ScanOptions::smaps`], see [`pss`](Self::pss).
+    pub swap_pss: Option<Bytes>,
 }
 
 impl Add for MemoryUsage {
@@ -28,10 +87,22 @@ impl Add for MemoryUsage {
             memory: self.memory + other.memory,
             resident: self.resident + other.resident,
             shared: self.shared + other.shared,
+            swap: self.swap + other.swap,
+            pss: add_optional(self.pss, other.pss),
+            uss: add_optional(self.uss, other.uss),
+            swap_pss: add_optional(self.swap_pss, other.swap_pss),
         }
     }
 }
 
+/// Adds values that may not have been collected, treating `None` as missing
+pub(crate) fn add_optional<T: Add<Output = T>>(a: Option<T>, b: Option<T>) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a + b),
+        (a, b) => a.or(b),
+    }
+}
+
 impl Sum for MemoryUsage {
     fn sum<I>(iter: I) -> Self
     where
@@ -43,12 +114,44 @@ impl Sum for MemoryUsage {
 
 /// Information about groups of processes with the same name
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
 pub struct ProcessGroups {
     /// PID to memory usage mapping
     pid_to_usage: HashMap<i32, MemoryUsage>,
 
     /// Total memory usage for all PIDs
     usage_totals: MemoryUsage,
+
+    /// PID to CPU time mapping
+    pid_to_cpu: HashMap<i32, CpuTime>,
+
+    /// Total CPU time for all PIDs
+    cpu_totals: CpuTime,
+
+    /// PID to start time mapping, in clock ticks after boot
+    pid_to_start_time: HashMap<i32, u64>,
+
+    /// PID to I/O mapping, for PIDs whose I/O could be read
+    pid_to_io: HashMap<i32, IoUsage>,
+
+    /// Total I/O for all PIDs whose I/O could be read
+    io_totals: IoUsage,
+
+    /// PID to thread and file descriptor counts mapping
+    pid_to_counts: HashMap<i32, ResourceCounts>,
+
+    /// Total thread and file descriptor counts for all PIDs
+    counts_totals: ResourceCounts,
+
+    /// PID to process information mapping
+    pid_to_info: HashMap<i32, ProcessInfo>,
+
+    /// Tree of the PIDs, when grouped by [`GroupBy::Ancestor`]
+    tree: Option<ProcessTree>,
 }
 
 impl ProcessGroups {
@@ -62,62 +165,314 @@ impl ProcessGroups {
         self.usage_totals
     }
 
-    fn add_usage(&mut self, pid: i32, usage: MemoryUsage) {
-        self.pid_to_usage.insert(pid, usage);
-        self.usage_totals = self.usage_totals + usage;
+    /// PID to CPU time mapping
+    pub fn pid_to_cpu(&self) -> &HashMap<i32, CpuTime> {
+        &self.pid_to_cpu
+    }
+
+    /// Total CPU time for all PIDs
+    ///
+    /// Use a [`Sampler`] to turn CPU times into CPU usage.
+    pub fn cpu_totals(&self) -> CpuTime {
+        self.cpu_totals
+    }
+
+    /// PID to the time the process started after boot, in clock ticks
+    ///
+    /// Lets a [`Sampler`] tell a process from a later one that reused its PID.
+    pub fn pid_to_start_time(&self) -> &HashMap<i32, u64> {
+        &self.pid_to_start_time
+    }
+
+    /// PID to I/O mapping, for PIDs whose I/O could be read
+    ///
+    /// Only collected with [`ScanOptions::io`].
+    pub fn pid_to_io(&self) -> &HashMap<i32, IoUsage> {
+        &self.pid_to_io
+    }
+
+    /// Total I/O for all PIDs whose I/O could be read
+    ///
+    /// Use a [`Sampler`] to turn I/O totals into rates.
+    pub fn io_totals(&self) -> IoUsage {
+        self.io_totals
+    }
+
+    /// PID to thread and file descriptor counts mapping
+    pub fn pid_to_counts(&self) -> &HashMap<i32, ResourceCounts> {
+        &self.pid_to_counts
+    }
+
+    /// Total thread and file descriptor counts for all PIDs
+    pub fn counts_totals(&self) -> ResourceCounts {
+        self.counts_totals
+    }
+
+    /// PID to process information mapping
+    ///
+    /// Only collected with [`ScanOptions::info`].
+    pub fn pid_to_info(&self) -> &HashMap<i32, ProcessInfo> {
+        &self.pid_to_info
+    }
+
+    /// Tree of the PIDs, when grouped by [`GroupBy::Ancestor`]
+    pub fn tree(&self) -> Option<&ProcessTree> {
+        self.tree.as_ref()
     }
+
+    fn add_process(&mut self, pid: i32, stats: ProcessStats) {
+        self.pid_to_usage.insert(pid, stats.usage);
+        self.usage_totals = self.usage_totals + stats.usage;
+        self.pid_to_cpu.insert(pid, stats.cpu);
+        self.cpu_totals = self.cpu_totals + stats.cpu;
+        self.pid_to_start_time.insert(pid, stats.start_time);
+        if let Some(io) = stats.io {
+            self.pid_to_io.insert(pid, io);
+            self.io_totals = self.io_totals + io;
+        }
+        self.pid_to_counts.insert(pid, stats.counts);
+        self.counts_totals = self.counts_totals + stats.counts;
+        if let Some(info) = stats.info {
+            self.pid_to_info.insert(pid, info);
+        }
+    }
+}
+
+/// Everything collected about a single process
+struct ProcessStats {
+    usage: MemoryUsage,
+    cpu: CpuTime,
+    start_time: u64,
+    io: Option<IoUsage>,
+    counts: ResourceCounts,
+    info: Option<ProcessInfo>,
+}
+
+/// Options controlling how processes are scanned
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    /// How processes are grouped
+    pub group_by: GroupBy,
+
+    /// Whether to read `/proc/[pid]/smaps_rollup` for [`MemoryUsage::pss`],
+    /// [`MemoryUsage::uss`] and [`MemoryUsage::swap_pss`]
+    ///
+    /// This is considerably slower than reading only `/proc/[pid]/status`, and requires the
+    /// same permissions as reading the process' memory maps.
+    pub smaps: bool,
+
+    /// Whether to read `/proc/[pid]/io` for [`ProcessGroups::pid_to_io`]
+    ///
+    /// This requires the same permissions as reading the process' memory maps, so processes of
+    /// other users are usually missing unless running as root.
+    pub io: bool,
+
+    /// Whether to count entries in `/proc/[pid]/fd` for [`ResourceCounts::fds`]
+    ///
+    /// Like [`io`](Self::io), this usually only works for processes of other users when
+    /// running as root.
+    pub fds: bool,
+
+    /// Whether to read the command line and executable path of each process for
+    /// [`ProcessGroups::pid_to_info`]
+    pub info: bool,
 }
 
-/// Running processes grouped by exe name
+/// Running processes grouped by a [`GroupBy`] key
+///
+/// With the `serde` feature, this implements `Serialize` and `Deserialize`. Group names are
+/// serialized as strings, replacing invalid UTF-8 with U+FFFD, and [`errors`](Self::errors)
+/// are not serialized. Fields missing when deserializing are left empty, so that data
+/// serialized before a field was added can still be read.
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
 pub struct GroupedProcess {
-    /// Mapping from process name to usage
+    /// Mapping from group name to usage
+    #[cfg_attr(feature = "serde", serde(with = "os_str_keys"))]
     name_to_group: HashMap<OsString, ProcessGroups>,
+
+    /// Which processes were included in the groups
+    coverage: Coverage,
+
+    /// Errors for processes that could not be read
+    #[cfg_attr(feature = "serde", serde(skip))]
+    errors: Vec<ProcessError>,
 }
 
 impl GroupedProcess {
-    /// Creates a new `GroupedProcess` by querying all running processes
-    pub fn new() -> Result<Self, procfs::ProcError> {
+    /// Creates a new `GroupedProcess` by querying all running processes, grouped by exe name
+    pub fn new() -> Result<Self> {
+        Self::with_group_by(&GroupBy::default())
+    }
+
+    /// Creates a new `GroupedProcess` by querying all running processes, grouped by `group_by`
+    pub fn with_group_by(group_by: &GroupBy) -> Result<Self> {
+        Self::scan(&ScanOptions {
+            group_by: group_by.clone(),
+            ..Default::default()
+        })
+    }
+
+    /// Creates a new `GroupedProcess` by querying all running processes as described by
+    /// `options`
+    ///
+    /// Processes that cannot be read are skipped and recorded in [`coverage`](Self::coverage)
+    /// and [`errors`](Self::errors), so this only fails if `/proc` itself cannot be listed.
+    pub fn scan(options: &ScanOptions) -> Result<Self> {
+        let group_by = &options.group_by;
         let procs = procfs::process::all_processes()?;
+        let table = match group_by {
+            GroupBy::Ancestor(_) => Some(ProcessTable::new(&procs)),
+            _ => None,
+        };
         let mut procs_grouped: HashMap<OsString, ProcessGroups> = HashMap::new();
+        let mut name_to_ancestor: HashMap<OsString, i32> = HashMap::new();
+        let mut coverage = Coverage::default();
+        let mut errors = Vec::new();
         for proc in procs {
-            let exe = if let Ok(exe) = proc.exe() {
-                exe
-            } else {
+            if is_kernel_thread(&proc) {
+                coverage.kernel_threads += 1;
                 continue;
+            }
+            let name = match (group_by, &table) {
+                (GroupBy::Ancestor(rollup), Some(table)) => {
+                    table.ancestor(proc.pid(), rollup).map(|ancestor| {
+                        let name = OsString::from(table.ancestor_name(ancestor));
+                        name_to_ancestor.insert(name.clone(), ancestor);
+                        name
+                    })
+                }
+                _ => group_by.key(&proc),
             };
-            let basename = exe.file_name().expect("Failed to get basename").to_owned();
-            let status = if let Ok(status) = proc.status() {
-                status
+            let name = if let Some(name) = name {
+                name
             } else {
+                coverage.skip(SkipReason::NoKey);
                 continue;
             };
-            let resident = if let Some(resident) = status.vmrss {
-                resident
-            } else {
-                continue;
+            let read = proc.status().map_err(Error::from).and_then(|status| {
+                let usage = read_memory_usage(&proc, &status, options.smaps)?;
+                Ok((status, usage))
+            });
+            let (status, usage) = match read {
+                Ok((status, Some(usage))) => (status, usage),
+                Ok((_, None)) if proc.stat.state == 'Z' => {
+                    coverage.skip(SkipReason::Zombie);
+                    continue;
+                }
+                Ok((_, None)) => {
+                    coverage.skip(SkipReason::NoMemoryInfo);
+                    continue;
+                }
+                Err(error) => {
+                    coverage.skip(SkipReason::from_error(&error));
+                    errors.push(ProcessError {
+                        pid: proc.pid(),
+                        error,
+                    });
+                    continue;
+                }
             };
-            let shared = status.rssshmem.unwrap();
-            let memory = resident - shared;
-            let usage = MemoryUsage {
-                memory,
-                resident,
-                shared,
+
+            let stats = ProcessStats {
+                usage,
+                cpu: CpuTime::from_process(&proc),
+                start_time: proc.stat.starttime,
+                io: if options.io {
+                    IoUsage::read(&proc).ok()
+                } else {
+                    None
+                },
+                counts: ResourceCounts::read(&proc, options.fds),
+                info: if options.info {
+                    Some(ProcessInfo::read(&proc, &status))
+                } else {
+                    None
+                },
             };
 
             procs_grouped
-                .entry(basename)
-                .or_insert(Default::default())
-                .add_usage(proc.pid(), usage);
+                .entry(name)
+                .or_default()
+                .add_process(proc.pid(), stats);
+            coverage.included += 1;
+        }
+
+        if let Some(table) = &table {
+            for (name, group) in procs_grouped.iter_mut() {
+                let root = name_to_ancestor[name];
+                group.tree = Some(table.subtree(root, group.pid_to_usage.keys().copied()));
+            }
         }
 
         Ok(GroupedProcess {
             name_to_group: procs_grouped,
+            coverage,
+            errors,
         })
     }
 
-    /// Name of process to process groups
+    /// Group name to process groups
     pub fn name_to_group(&self) -> &HashMap<OsString, ProcessGroups> {
         &self.name_to_group
     }
+
+    /// Which processes were included in the groups
+    pub fn coverage(&self) -> &Coverage {
+        &self.coverage
+    }
+
+    /// Errors for processes that could not be read
+    pub fn errors(&self) -> &[ProcessError] {
+        &self.errors
+    }
+}
+
+/// Reads the memory usage of a process, or `None` if it has no user space memory
+///
+/// `RssShmem` is only reported since Linux 4.5. On older kernels, the resident and shared
+/// sizes are taken from `/proc/[pid]/statm` instead, where shared also includes file backed
+/// pages.
+fn read_memory_usage(proc: &Process, status: &Status, smaps: bool) -> Result<Option<MemoryUsage>> {
+    let resident = if let Some(resident) = status.vmrss {
+        resident
+    } else {
+        return Ok(None);
+    };
+    let (resident, shared) = match status.rssshmem {
+        Some(shared) => (Bytes::from_kib(resident), Bytes::from_kib(shared)),
+        None => {
+            let statm = proc.statm()?;
+            let page_size = procfs::page_size()? as u64;
+            (
+                Bytes::new(statm.resident * page_size),
+                Bytes::new(statm.shared * page_size),
+            )
+        }
+    };
+    let smaps = if smaps {
+        SmapsTotals::read(proc.pid()).ok()
+    } else {
+        None
+    };
+    Ok(Some(MemoryUsage {
+        memory: resident.saturating_sub(shared),
+        resident,
+        shared,
+        swap: Bytes::from_kib(status.vmswap.unwrap_or(0)),
+        pss: smaps.map(|smaps| Bytes::from_kib(smaps.pss)),
+        uss: smaps.map(|smaps| Bytes::from_kib(smaps.uss)),
+        swap_pss: smaps.map(|smaps| Bytes::from_kib(smaps.swap_pss)),
+    }))
+}
+
+/// Whether a process is a kernel thread, based on the `PF_KTHREAD` flag
+fn is_kernel_thread(proc: &Process) -> bool {
+    proc.stat
+        .flags()
+        .is_ok_and(|flags| flags.contains(StatFlags::PF_KTHREAD))
 }
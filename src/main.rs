@@ -1,23 +1,798 @@
 //! Prints information about memory usage of running processes
 
+use std::cmp::Ordering;
+use std::error::Error;
 use std::ffi::OsStr;
+use std::io;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
 
-use size_format;
+use clap::{ArgEnum, Parser, Subcommand};
 use top_group::*;
 
+use crate::columns::{Column, Row};
+use crate::detail::Detail;
+use crate::filter::Filter;
+use crate::prometheus::MetricNaming;
+
+mod alert;
+mod columns;
+mod delimited;
+mod detail;
+#[cfg(feature = "serde")]
+mod diff;
+mod filter;
+#[cfg(feature = "history")]
+mod history;
+#[cfg(feature = "interactive")]
+mod interactive;
+mod json;
+mod prometheus;
+mod serve;
+mod signal;
+mod time;
+mod watch;
+
+/// Key used to group processes
+#[derive(Debug, Clone, Copy, ArgEnum)]
+enum GroupByArg {
+    /// Basename of the executable
+    Exe,
+    /// Full path of the executable
+    ExePath,
+    /// Command name from /proc/[pid]/comm
+    Comm,
+    /// First command line argument
+    Cmdline,
+    /// Owning user (see --uid)
+    User,
+    /// Cgroup path
+    Cgroup,
+    /// Systemd unit derived from the cgroup path
+    SystemdUnit,
+    /// Systemd slice derived from the cgroup path
+    SystemdSlice,
+    /// Docker, Podman, containerd or CRI-O container
+    Container,
+    /// Kubernetes pod UID
+    Pod,
+    /// Ancestor process (see --tree-root and --tree-depth)
+    Tree,
+}
+
+/// UID used when grouping by user
+#[derive(Debug, Clone, Copy, ArgEnum)]
+enum UidArg {
+    Real,
+    Effective,
+}
+
+impl From<UidArg> for UidKind {
+    fn from(arg: UidArg) -> Self {
+        match arg {
+            UidArg::Real => UidKind::Real,
+            UidArg::Effective => UidKind::Effective,
+        }
+    }
+}
+
+/// How results are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+enum OutputFormat {
+    /// Human readable table
+    Text,
+    /// JSON document, see the schema in src/json.rs
+    Json,
+    /// Comma-separated values with a header row
+    Csv,
+    /// Tab-separated values with a header row
+    Tsv,
+}
+
+/// Direction in which groups are listed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+enum Order {
+    /// Smallest first, so the largest groups end up next to the prompt
+    Asc,
+    /// Largest first
+    Desc,
+}
+
+/// How sizes are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+enum Units {
+    /// Powers of 1024 (KiB, MiB, GiB), like `free -h` and `smem -k`
+    Iec,
+    /// Powers of 1000 (kB, MB, GB)
+    Si,
+    /// Exact number of bytes
+    Raw,
+}
+
+impl Units {
+    fn format(self, bytes: Bytes) -> String {
+        match self {
+            Units::Iec => format!("{}B", size_format::SizeFormatterBinary::new(bytes.as_u64())),
+            Units::Si => format!("{}B", size_format::SizeFormatterSI::new(bytes.as_u64())),
+            Units::Raw => bytes.as_u64().to_string(),
+        }
+    }
+}
+
+/// Figure to sort and display groups by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+enum SortKey {
+    /// Resident minus shared
+    Memory,
+    /// Resident set size
+    Resident,
+    /// Shared memory
+    Shared,
+    /// Swapped out memory
+    Swap,
+    /// Proportional set size
+    Pss,
+    /// Unique set size
+    Uss,
+    /// Proportional swap size
+    SwapPss,
+    /// Number of threads
+    Threads,
+    /// Number of open file descriptors
+    Fds,
+    /// Number of processes
+    Count,
+    /// Group name, displaying memory
+    Name,
+}
+
+impl SortKey {
+    fn value(self, usage: &MemoryUsage, counts: Option<&ResourceCounts>) -> u64 {
+        match self {
+            SortKey::Memory | SortKey::Name => usage.memory.as_u64(),
+            SortKey::Resident => usage.resident.as_u64(),
+            SortKey::Shared => usage.shared.as_u64(),
+            SortKey::Swap => usage.swap.as_u64(),
+            SortKey::Pss => usage.pss.map_or(0, Bytes::as_u64),
+            SortKey::Uss => usage.uss.map_or(0, Bytes::as_u64),
+            SortKey::SwapPss => usage.swap_pss.map_or(0, Bytes::as_u64),
+            SortKey::Threads => counts.map_or(0, |counts| counts.threads),
+            SortKey::Fds => counts.and_then(|counts| counts.fds).unwrap_or(0),
+            SortKey::Count => 1,
+        }
+    }
+
+    fn format(self, value: u64, units: Units) -> String {
+        match self {
+            SortKey::Threads | SortKey::Fds | SortKey::Count => value.to_string(),
+            _ => units.format(Bytes::new(value)),
+        }
+    }
+
+    fn group_value(self, group: &ProcessGroups) -> u64 {
+        match self {
+            SortKey::Count => group.pid_to_usage().len() as u64,
+            _ => self.value(&group.usage_totals(), Some(&group.counts_totals())),
+        }
+    }
+
+    /// Orders groups from smallest to largest, or alphabetically when sorting by name
+    fn compare(self, a: (&OsStr, &ProcessGroups), b: (&OsStr, &ProcessGroups)) -> Ordering {
+        match self {
+            SortKey::Name => a.0.cmp(b.0),
+            _ => (self.group_value(a.1), a.0).cmp(&(self.group_value(b.1), b.0)),
+        }
+    }
+
+    fn pid_value(self, group: &ProcessGroups, pid: i32) -> Option<u64> {
+        let usage = group.pid_to_usage().get(&pid)?;
+        Some(self.value(usage, group.pid_to_counts().get(&pid)))
+    }
+
+    /// Whether the figure is read from smaps_rollup
+    fn needs_smaps(self) -> bool {
+        matches!(self, SortKey::Pss | SortKey::Uss | SortKey::SwapPss)
+    }
+}
+
+/// Alternative modes of operation
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Serve per-group metrics in the Prometheus text format over HTTP
+    Serve {
+        /// Address to listen on, e.g. 0.0.0.0:9256
+        #[clap(long, value_name = "ADDR")]
+        listen: String,
+
+        /// Seconds for which a scan is reused for further scrapes
+        #[clap(long, value_name = "SECONDS", default_value = "5", parse(try_from_str = parse_seconds_or_zero))]
+        cache: Duration,
+
+        #[clap(flatten)]
+        naming: MetricNaming,
+    },
+
+    /// Write per-group metrics to a file for the node_exporter textfile collector, e.g. from
+    /// cron
+    Textfile {
+        /// File to replace atomically, e.g.
+        /// /var/lib/node_exporter/textfile_collector/top_group.prom
+        #[clap(long, value_name = "PATH")]
+        output: PathBuf,
+
+        #[clap(flatten)]
+        naming: MetricNaming,
+    },
+
+    /// Check per-group limits on every sample, printing a line and optionally running a
+    /// command when one is crossed and when it recovers
+    Alert {
+        /// Limit such as "postgres memory > 8GiB for 60s clear 7GiB" or "* fds > 10000"; see
+        /// the alert module documentation for the syntax
+        #[clap(
+            long = "rule",
+            value_name = "RULE",
+            required_unless_present = "rules-file"
+        )]
+        rules: Vec<alert::Rule>,
+
+        /// File with one rule per line; empty lines and lines starting with # are ignored
+        #[clap(long, value_name = "PATH")]
+        rules_file: Option<PathBuf>,
+
+        /// Shell command to run on every alert and recovery, with the environment variables
+        /// TOP_GROUP_EVENT (alert, recovered or gone), TOP_GROUP_GROUP, TOP_GROUP_VALUE (raw),
+        /// TOP_GROUP_VALUE_FORMATTED, TOP_GROUP_LIMIT and TOP_GROUP_RULE
+        #[clap(long, value_name = "COMMAND")]
+        command: Option<String>,
+
+        /// Seconds between samples
+        #[clap(long, value_name = "SECONDS", default_value = "5", parse(try_from_str = parse_seconds))]
+        interval: Duration,
+    },
+
+    /// Send a signal to every process of a group, as grouped by --group-by, after listing
+    /// them and asking for confirmation
+    Signal {
+        /// Group whose processes to signal
+        #[clap(value_name = "GROUP")]
+        group: String,
+
+        /// Signal name, e.g. TERM or SIGKILL, or number
+        #[clap(value_name = "SIGNAL")]
+        signal: signal::Signal,
+
+        /// Only list the processes that would be signalled
+        #[clap(long)]
+        dry_run: bool,
+
+        /// Do not ask for confirmation
+        #[clap(long, short)]
+        yes: bool,
+    },
+
+    /// Save a scan to a file, to compare it with a later one using `diff`
+    #[cfg(feature = "serde")]
+    Snapshot {
+        /// File to write
+        #[clap(value_name = "PATH")]
+        output: PathBuf,
+
+        /// Also read PSS, USS and swap PSS from smaps_rollup, which is slower
+        #[clap(long)]
+        smaps: bool,
+    },
+
+    /// Show which groups and processes grew, shrank, appeared or disappeared between two
+    /// snapshots, largest growth of --sort first
+    #[cfg(feature = "serde")]
+    Diff {
+        /// Earlier snapshot
+        #[clap(value_name = "OLD")]
+        old: PathBuf,
+
+        /// Later snapshot
+        #[clap(value_name = "NEW")]
+        new: PathBuf,
+    },
+
+    /// Sample periodically, appending per-group usage to a SQLite database for `history`
+    #[cfg(feature = "history")]
+    Record {
+        /// Database file, created if missing
+        #[clap(long, value_name = "PATH")]
+        database: PathBuf,
+
+        /// Seconds between samples
+        #[clap(long, value_name = "SECONDS", default_value = "60", parse(try_from_str = parse_seconds))]
+        interval: Duration,
+
+        /// Delete samples older than this, e.g. 7d or 12h
+        #[clap(long, value_name = "DURATION", parse(try_from_str = parse_duration))]
+        retention: Option<u64>,
+
+        /// Also record PSS, USS and swap PSS from smaps_rollup, which is slower
+        #[clap(long)]
+        smaps: bool,
+    },
+
+    /// Show the recorded --sort figure of a group over time, or of all groups in a time range
+    /// with the highest first
+    #[cfg(feature = "history")]
+    History {
+        /// Group to show, as recorded with the same --group-by
+        #[clap(value_name = "GROUP")]
+        group: Option<String>,
+
+        /// Database written by `record`
+        #[clap(long, value_name = "PATH")]
+        database: PathBuf,
+
+        /// Start of the range, e.g. 2h (ago) or "2024-03-01 03:00" (UTC)
+        #[clap(long, value_name = "TIME", default_value = "1d", parse(try_from_str = time::parse_time))]
+        since: u64,
+
+        /// End of the range, like --since; defaults to now
+        #[clap(long, value_name = "TIME", parse(try_from_str = time::parse_time))]
+        until: Option<u64>,
+
+        /// Combine the samples of each period, e.g. 10m or 1h, into one line
+        #[clap(long, value_name = "DURATION", parse(try_from_str = parse_duration))]
+        step: Option<u64>,
+    },
+}
+
+/// Parses a duration such as `90m` into seconds
+#[cfg(feature = "history")]
+fn parse_duration(s: &str) -> std::result::Result<u64, String> {
+    time::parse_duration(s)
+        .filter(|seconds| *seconds > 0)
+        .ok_or_else(|| format!("invalid duration '{}', expected e.g. 30s, 90m, 2h or 7d", s))
+}
+
+/// Shows memory usage of running processes grouped together
+#[derive(Debug, Parser)]
+#[clap(version, about)]
+struct Args {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    /// How to group processes
+    #[clap(long, arg_enum, value_name = "KEY", default_value = "exe")]
+    group_by: GroupByArg,
+
+    /// Figure to sort and display groups by
+    #[clap(long, arg_enum, value_name = "KEY", default_value = "memory")]
+    sort: SortKey,
+
+    /// Direction in which groups are listed
+    #[clap(long, arg_enum, default_value = "asc")]
+    order: Order,
+
+    /// Only show the N largest groups by --sort, or the first N names with --sort=name
+    #[clap(long, value_name = "N")]
+    top: Option<usize>,
+
+    #[clap(flatten)]
+    filter: Filter,
+
+    /// How sizes are printed
+    #[clap(long, arg_enum, default_value = "iec")]
+    units: Units,
+
+    /// Output format
+    #[clap(long, arg_enum, value_name = "FORMAT", default_value = "text")]
+    format: OutputFormat,
+
+    /// Columns to show instead of the --sort figure [default for csv and tsv:
+    /// name,processes,memory,resident,shared,swap, or name,pid,... with --per-pid]
+    #[clap(long, arg_enum, value_name = "COLUMN", use_value_delimiter = true)]
+    columns: Vec<Column>,
+
+    /// Print one row for each process instead of each group with --format csv and tsv
+    #[clap(long)]
+    per_pid: bool,
+
+    /// Also show CPU usage, sampled over --sample-interval
+    #[clap(long)]
+    cpu: bool,
+
+    /// Also show disk read and write rates, sampled over --sample-interval
+    #[clap(long)]
+    io: bool,
+
+    /// List the processes of each group with their command lines
+    #[clap(long)]
+    detail: bool,
+
+    /// Like --detail, but with processes nested below their parents
+    #[clap(long)]
+    tree: bool,
+
+    /// Also show thread and open file descriptor counts
+    #[clap(long)]
+    counts: bool,
+
+    /// Rescan every SECONDS, printing changes since the previous and the first sample
+    #[clap(long, value_name = "SECONDS", parse(try_from_str = parse_seconds))]
+    watch: Option<Duration>,
+
+    /// Show a full-screen, continuously updated table
+    #[cfg(feature = "interactive")]
+    #[clap(short, long)]
+    interactive: bool,
+
+    /// Seconds over which CPU usage and disk I/O are sampled, and between refreshes in
+    /// interactive mode
+    #[clap(long, value_name = "SECONDS", default_value = "1", parse(try_from_str = parse_seconds))]
+    sample_interval: Duration,
+
+    /// UID to use with --group-by=user
+    #[clap(long, arg_enum, default_value = "real")]
+    uid: UidArg,
+
+    /// passwd(5) file used to resolve user names
+    #[clap(long, value_name = "PATH", default_value = DEFAULT_PASSWD_PATH)]
+    passwd: PathBuf,
+
+    /// Docker container metadata directory used to name containers
+    #[clap(long, value_name = "PATH", default_value = DEFAULT_DOCKER_ROOT)]
+    docker_root: PathBuf,
+
+    /// Podman container metadata directory used to name containers
+    #[clap(long, value_name = "PATH", default_value = DEFAULT_PODMAN_ROOT)]
+    podman_root: PathBuf,
+
+    /// PID whose descendants are grouped with --group-by=tree
+    #[clap(long, value_name = "PID", default_value = "1")]
+    tree_root: i32,
+
+    /// Levels below --tree-root at which processes are grouped with --group-by=tree
+    #[clap(long, value_name = "N", default_value = "1")]
+    tree_depth: usize,
+}
+
+impl Args {
+    fn group_by(&self) -> std::result::Result<GroupBy, String> {
+        Ok(match self.group_by {
+            GroupByArg::Exe => GroupBy::ExeBasename,
+            GroupByArg::ExePath => GroupBy::ExePath,
+            GroupByArg::Comm => GroupBy::Comm,
+            GroupByArg::Cmdline => GroupBy::Cmdline,
+            GroupByArg::User => GroupBy::user(self.uid.into(), &self.passwd)
+                .map_err(|e| format!("Failed to read {}: {}", self.passwd.display(), e))?,
+            GroupByArg::Cgroup => GroupBy::Cgroup,
+            GroupByArg::SystemdUnit => GroupBy::SystemdUnit,
+            GroupByArg::SystemdSlice => GroupBy::SystemdSlice,
+            GroupByArg::Container => GroupBy::Container(self.container_names()),
+            GroupByArg::Pod => GroupBy::Pod,
+            GroupByArg::Tree => GroupBy::Ancestor(TreeRollup {
+                root: self.tree_root,
+                depth: self.tree_depth,
+            }),
+        })
+    }
+
+    /// Name of --group-by as given on the command line, e.g. `exe`
+    #[cfg(feature = "history")]
+    fn group_by_name(&self) -> &'static str {
+        match self.group_by.to_possible_value() {
+            Some(value) => value.get_name(),
+            None => "",
+        }
+    }
+
+    /// Container names from all runtimes, skipping runtimes that are not installed
+    fn container_names(&self) -> ContainerNames {
+        let mut names = ContainerNames::new();
+        let results = [
+            ("Docker", names.read_docker(&self.docker_root)),
+            ("Podman", names.read_podman(&self.podman_root)),
+        ];
+        for (runtime, result) in results {
+            match result {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    eprintln!("Failed to read {} container names: {}", runtime, e);
+                }
+                _ => {}
+            }
+        }
+        names
+    }
+}
+
+/// Parses a positive number of seconds such as `0.5`
+fn parse_seconds(s: &str) -> std::result::Result<Duration, String> {
+    match parse_seconds_or_zero(s)? {
+        seconds if seconds.is_zero() => {
+            Err(format!("invalid interval '{}', must be more than 0", s))
+        }
+        seconds => Ok(seconds),
+    }
+}
+
+/// Parses a number of seconds such as `0.5`, allowing 0
+fn parse_seconds_or_zero(s: &str) -> std::result::Result<Duration, String> {
+    s.parse()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| format!("invalid number of seconds '{}'", s))
+}
+
 fn main() {
-    let procs_grouped = GroupedProcess::new().expect("Failed to get processes");
-    println!("{:#?}", procs_grouped);
+    if let Err(e) = run(Args::parse()) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: Args) -> std::result::Result<(), Box<dyn Error>> {
+    let options = ScanOptions {
+        group_by: args.group_by()?,
+        smaps: args.sort.needs_smaps() || args.columns.iter().any(|column| column.needs_smaps()),
+        io: args.io,
+        fds: args.counts || args.sort == SortKey::Fds || args.columns.contains(&Column::Fds),
+        info: args.detail || args.tree,
+    };
+
+    match &args.command {
+        Some(Command::Serve {
+            listen,
+            cache,
+            naming,
+        }) => return serve::run(options, listen, *cache, naming.clone()),
+        Some(Command::Textfile { output, naming }) => {
+            return prometheus::write_textfile(options, output, naming)
+        }
+        Some(Command::Alert {
+            rules,
+            rules_file,
+            command,
+            interval,
+        }) => {
+            let mut rules = rules.clone();
+            if let Some(path) = rules_file {
+                rules.extend(alert::read_rules(path)?);
+            }
+            let alerts = alert::Alerts {
+                rules,
+                units: args.units,
+                command: command.clone(),
+            };
+            let sampler = Sampler::new(alerts.scan_options(options))?;
+            return alerts.run(sampler, *interval);
+        }
+        Some(Command::Signal {
+            group,
+            signal,
+            dry_run,
+            yes,
+        }) => {
+            let request = signal::Request {
+                group,
+                signal: *signal,
+                dry_run: *dry_run,
+                yes: *yes,
+                users: UserNames::from_file(&args.passwd).unwrap_or_default(),
+            };
+            return request.run(&options);
+        }
+        #[cfg(feature = "serde")]
+        Some(Command::Snapshot { output, smaps }) => {
+            let options = ScanOptions {
+                smaps: options.smaps || *smaps,
+                fds: true,
+                info: true,
+                ..options
+            };
+            return diff::save(&options, output);
+        }
+        #[cfg(feature = "serde")]
+        Some(Command::Diff { old, new }) => {
+            let load = |path: &PathBuf| {
+                Snapshot::load(path)
+                    .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+            };
+            let (old, new) = (load(old)?, load(new)?);
+            let diff = diff::Diff {
+                sort: args.sort,
+                units: args.units,
+                filter: &args.filter,
+            };
+            diff.print(&old, &new);
+            return Ok(());
+        }
+        #[cfg(feature = "history")]
+        Some(Command::Record {
+            database,
+            interval,
+            retention,
+            smaps,
+        }) => {
+            let options = ScanOptions {
+                smaps: options.smaps || *smaps,
+                fds: true,
+                ..options
+            };
+            return history::record(
+                options,
+                args.group_by_name(),
+                database,
+                *interval,
+                *retention,
+            );
+        }
+        #[cfg(feature = "history")]
+        Some(Command::History {
+            group,
+            database,
+            since,
+            until,
+            step,
+        }) => {
+            let query = history::Query {
+                group_by: args.group_by_name(),
+                sort: args.sort,
+                units: args.units,
+                since: *since,
+                until: until.unwrap_or_else(time::now),
+            };
+            return match group {
+                Some(group) => query.print_group(database, group, *step),
+                None => query.print_groups(database, args.top),
+            };
+        }
+        None => {}
+    }
 
-    let mut proc_group_usage: Vec<(&OsStr, u64)> = procs_grouped
+    #[cfg(feature = "interactive")]
+    if args.interactive {
+        return interactive::run(options, args.sample_interval, args.units);
+    }
+
+    let mut sampler = Sampler::new(options)?;
+    if let Some(interval) = args.watch {
+        return Ok(watch::run(
+            sampler,
+            args.sort,
+            &args.filter,
+            args.units,
+            interval,
+        )?);
+    }
+    if args.cpu || args.io {
+        sampler.sample()?;
+        thread::sleep(args.sample_interval);
+    }
+    let sample = sampler.sample()?;
+    let procs_grouped = sample.processes();
+
+    let mut proc_group_usage: Vec<(&OsStr, &ProcessGroups)> = procs_grouped
         .name_to_group()
         .iter()
-        .map(|(name, group)| (name.as_os_str(), group.usage_totals().memory))
+        .map(|(name, group)| (name.as_os_str(), group))
+        .filter(|(name, group)| args.filter.matches(name, group))
+        .collect();
+    proc_group_usage.sort_unstable_by(|a, b| args.sort.compare(*a, *b));
+    if let Some(top) = args.top {
+        if args.sort == SortKey::Name {
+            proc_group_usage.truncate(top);
+        } else {
+            proc_group_usage.drain(..proc_group_usage.len().saturating_sub(top));
+        }
+    }
+    if args.order == Order::Desc {
+        proc_group_usage.reverse();
+    }
+    match args.format {
+        OutputFormat::Text => {}
+        OutputFormat::Json => {
+            json::print(&sample, &proc_group_usage);
+            return Ok(());
+        }
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let delimiter = if args.format == OutputFormat::Csv {
+                b','
+            } else {
+                b'\t'
+            };
+            let columns = if args.columns.is_empty() {
+                Column::defaults(args.per_pid)
+            } else {
+                args.columns.clone()
+            };
+            delimited::print(&proc_group_usage, &columns, delimiter, args.per_pid)?;
+            return Ok(());
+        }
+    }
+    let text_columns: Vec<Column> = args
+        .columns
+        .iter()
+        .copied()
+        .filter(|column| *column != Column::Name)
         .collect();
-    proc_group_usage.sort_unstable_by_key(|(name, usage)| -> (u64, &OsStr) { (*usage, *name) });
-    for (name, usage) in proc_group_usage {
-        let usage_bytes = usage * 1000;
-        let usage = format!("{}B", size_format::SizeFormatterSI::new(usage_bytes));
-        println!("{:30} {}", name.to_string_lossy(), usage);
+    if !text_columns.is_empty() {
+        let titles: String = text_columns
+            .iter()
+            .map(|column| format!(" {:>10}", column.title()))
+            .collect();
+        println!("{:30}{}", Column::Name.title(), titles);
+    }
+    let detail = if args.detail || args.tree {
+        let detail = Detail {
+            users: UserNames::from_file(&args.passwd).unwrap_or_default(),
+            sort: args.sort,
+            units: args.units,
+            tree: args.tree,
+            uptime: procfs::Uptime::new().map_or(0.0, |uptime| uptime.uptime),
+            ticks_per_second: procfs::ticks_per_second().map_or(100, |ticks| ticks as u64),
+        };
+        println!("{}", detail.header());
+        Some(detail)
+    } else {
+        None
+    };
+    for (name, group) in proc_group_usage {
+        let figures: String = if text_columns.is_empty() {
+            format!(
+                " {:>10}",
+                args.sort.format(args.sort.group_value(group), args.units)
+            )
+        } else {
+            let row = Row::group(name, group);
+            text_columns
+                .iter()
+                .map(|column| format!(" {:>10}", column.formatted(&row, args.units)))
+                .collect()
+        };
+        let mut columns = String::new();
+        if args.counts {
+            let counts = group.counts_totals();
+            let fds = counts
+                .fds
+                .map_or_else(|| "-".to_owned(), |fds| fds.to_string());
+            columns += &format!(" {:>6} thr {:>7} fds", counts.threads, fds);
+        }
+        if let Some(group_rates) = sample.name_to_rates().get(name) {
+            if args.cpu {
+                columns += &format!(" {:6.1}%", group_rates.cpu_percent);
+            }
+            if args.io {
+                columns += &format!(
+                    " {:>10}/s {:>10}/s",
+                    args.units
+                        .format(Bytes::new(group_rates.io.read_bytes as u64)),
+                    args.units
+                        .format(Bytes::new(group_rates.io.write_bytes as u64))
+                );
+            }
+        }
+        println!("{:30}{}{}", name.to_string_lossy(), figures, columns);
+        if let Some(detail) = &detail {
+            detail.print(group);
+        } else if let Some(tree) = group.tree() {
+            print_tree(tree, group, args.sort, args.units);
+        }
+    }
+    println!("{}", procs_grouped.coverage());
+    Ok(())
+}
+
+/// Prints the processes of a group indented below the group
+fn print_tree(tree: &ProcessTree, group: &ProcessGroups, sort: SortKey, units: Units) {
+    for (depth, pid) in tree.walk() {
+        let label = format!(
+            "{:indent$}{} {}",
+            "",
+            pid,
+            tree.name(pid).unwrap_or("?"),
+            indent = 2 * (depth + 1)
+        );
+        let usage = match sort.pid_value(group, pid) {
+            Some(value) => sort.format(value, units),
+            None => "-".to_owned(),
+        };
+        println!("{:30} {:>10}", label, usage);
     }
 }
@@ -0,0 +1,30 @@
+//! Serde helpers for maps keyed by group name
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Serializes the keys as strings, replacing invalid UTF-8 with U+FFFD
+pub(crate) fn serialize<V, S>(map: &HashMap<OsString, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    V: Serialize,
+    S: Serializer,
+{
+    serializer.collect_map(
+        map.iter()
+            .map(|(key, value)| (key.to_string_lossy(), value)),
+    )
+}
+
+pub(crate) fn deserialize<'de, V, D>(deserializer: D) -> Result<HashMap<OsString, V>, D::Error>
+where
+    V: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    let map = HashMap::<String, V>::deserialize(deserializer)?;
+    Ok(map
+        .into_iter()
+        .map(|(key, value)| (key.into(), value))
+        .collect())
+}
@@ -0,0 +1,48 @@
+//! Identity of a process from `/proc/[pid]`
+
+use std::path::PathBuf;
+
+use procfs::process::{Process, Status};
+
+/// What a process is, as opposed to how much it uses
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProcessInfo {
+    /// Command line arguments, empty for zombies and processes whose command line cannot be
+    /// read
+    pub cmdline: Vec<String>,
+
+    /// Command name from `/proc/[pid]/comm`, truncated by the kernel to 15 bytes
+    pub comm: String,
+
+    /// Path of the executable, `None` if it cannot be read
+    pub exe: Option<PathBuf>,
+
+    /// Parent PID
+    pub ppid: i32,
+
+    /// Real UID
+    pub uid: u32,
+
+    /// Time the process started after system boot, in clock ticks
+    ///
+    /// Together with the PID, this identifies a process even if the PID is reused.
+    pub start_time: u64,
+
+    /// State from `/proc/[pid]/stat`, e.g. `R` (running), `S` (sleeping) or `Z` (zombie)
+    pub state: char,
+}
+
+impl ProcessInfo {
+    pub(crate) fn read(proc: &Process, status: &Status) -> Self {
+        ProcessInfo {
+            cmdline: proc.cmdline().unwrap_or_default(),
+            comm: proc.stat.comm.clone(),
+            exe: proc.exe().ok(),
+            ppid: proc.stat.ppid,
+            uid: status.ruid,
+            start_time: proc.stat.starttime,
+            state: proc.stat.state,
+        }
+    }
+}
@@ -0,0 +1,425 @@
+//! Prometheus text exposition format
+
+use std::error::Error;
+use std::ffi::{OsStr, OsString};
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, Write as _};
+use std::path::Path;
+use std::process;
+
+use clap::Args;
+use top_group::{Bytes, Coverage, GroupedProcess, ProcessGroups, ScanOptions};
+
+/// Names of the metrics and of the label holding the group name
+#[derive(Debug, Clone, Args)]
+pub struct MetricNaming {
+    /// Prefix of all metric names
+    #[clap(long, value_name = "PREFIX", default_value = "top_group")]
+    pub metric_prefix: String,
+
+    /// Name of the label holding the group name
+    #[clap(long, value_name = "NAME", default_value = "group")]
+    pub group_label: String,
+}
+
+impl MetricNaming {
+    /// Checks that the prefix and label are valid metric and label names
+    pub fn validate(&self) -> Result<(), String> {
+        let valid = |name: &str, colon: bool| {
+            let mut chars = name.chars();
+            chars
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || (colon && c == ':'))
+                && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || (colon && c == ':'))
+        };
+        if !valid(&self.metric_prefix, true) {
+            return Err(format!("Invalid metric prefix {:?}", self.metric_prefix));
+        }
+        if !valid(&self.group_label, false) || self.group_label.starts_with("__") {
+            return Err(format!("Invalid label name {:?}", self.group_label));
+        }
+        Ok(())
+    }
+
+    fn metric(&self, name: &str) -> String {
+        format!("{}_{}", self.metric_prefix, name)
+    }
+}
+
+/// A metric with one sample per group
+///
+/// Even cumulative figures like CPU time are gauges: they are sums over the processes in the
+/// group at the time of the scan, so they go down when a process exits, which Prometheus would
+/// take for a counter reset.
+struct GroupMetric {
+    name: &'static str,
+    kind: &'static str,
+    help: &'static str,
+    /// Value of a group given the clock ticks per second, or `None` to leave the group out,
+    /// e.g. when it was not collected
+    value: fn(&ProcessGroups, f64) -> Option<f64>,
+}
+
+const GROUP_METRICS: &[GroupMetric] = &[
+    GroupMetric {
+        name: "memory_bytes",
+        kind: "gauge",
+        help: "Resident minus shared memory of the processes in the group",
+        value: |group, _| Some(group.usage_totals().memory.as_u64() as f64),
+    },
+    GroupMetric {
+        name: "resident_bytes",
+        kind: "gauge",
+        help: "Resident set size of the processes in the group",
+        value: |group, _| Some(group.usage_totals().resident.as_u64() as f64),
+    },
+    GroupMetric {
+        name: "shared_bytes",
+        kind: "gauge",
+        help: "Shared memory of the processes in the group",
+        value: |group, _| Some(group.usage_totals().shared.as_u64() as f64),
+    },
+    GroupMetric {
+        name: "swap_bytes",
+        kind: "gauge",
+        help: "Swapped out memory of the processes in the group",
+        value: |group, _| Some(group.usage_totals().swap.as_u64() as f64),
+    },
+    GroupMetric {
+        name: "pss_bytes",
+        kind: "gauge",
+        help: "Proportional set size of the processes in the group",
+        value: |group, _| group.usage_totals().pss.map(|bytes| bytes.as_u64() as f64),
+    },
+    GroupMetric {
+        name: "uss_bytes",
+        kind: "gauge",
+        help: "Unique set size of the processes in the group",
+        value: |group, _| group.usage_totals().uss.map(|bytes| bytes.as_u64() as f64),
+    },
+    GroupMetric {
+        name: "swap_pss_bytes",
+        kind: "gauge",
+        help: "Proportional swap size of the processes in the group",
+        value: |group, _| {
+            group
+                .usage_totals()
+                .swap_pss
+                .map(|bytes| bytes.as_u64() as f64)
+        },
+    },
+    GroupMetric {
+        name: "processes",
+        kind: "gauge",
+        help: "Number of processes in the group",
+        value: |group, _| Some(group.pid_to_usage().len() as f64),
+    },
+    GroupMetric {
+        name: "threads",
+        kind: "gauge",
+        help: "Number of threads of the processes in the group",
+        value: |group, _| Some(group.counts_totals().threads as f64),
+    },
+    GroupMetric {
+        name: "open_fds",
+        kind: "gauge",
+        help: "Number of open file descriptors of the processes in the group",
+        value: |group, _| group.counts_totals().fds.map(|fds| fds as f64),
+    },
+    GroupMetric {
+        name: "cpu_seconds",
+        kind: "gauge",
+        help: "CPU time consumed so far by the processes currently in the group, drops when \
+               one of them exits",
+        value: |group, ticks| Some(group.cpu_totals().total() as f64 / ticks),
+    },
+    GroupMetric {
+        name: "read_bytes",
+        kind: "gauge",
+        help: "Bytes read from storage so far by the processes currently in the group, drops \
+               when one of them exits",
+        value: |group, _| io_total(group, group.io_totals().read_bytes),
+    },
+    GroupMetric {
+        name: "written_bytes",
+        kind: "gauge",
+        help: "Bytes written to storage so far by the processes currently in the group, drops \
+               when one of them exits",
+        value: |group, _| io_total(group, group.io_totals().write_bytes),
+    },
+];
+
+/// An I/O total, or `None` if the I/O of no process in the group was read
+fn io_total(group: &ProcessGroups, bytes: Bytes) -> Option<f64> {
+    if group.pid_to_io().is_empty() {
+        None
+    } else {
+        Some(bytes.as_u64() as f64)
+    }
+}
+
+/// Renders all groups of a scan, with the group name in a label
+pub fn render(processes: &GroupedProcess, ticks_per_second: u64, naming: &MetricNaming) -> String {
+    let mut groups: Vec<(&OsStr, &ProcessGroups)> = processes
+        .name_to_group()
+        .iter()
+        .map(|(name, group)| (name.as_os_str(), group))
+        .collect();
+    groups.sort_unstable_by_key(|(name, _)| *name);
+
+    let mut out = String::new();
+    render_groups(&mut out, &groups, ticks_per_second as f64, naming);
+    render_coverage(&mut out, processes.coverage(), naming);
+    out
+}
+
+fn render_groups(
+    out: &mut String,
+    groups: &[(&OsStr, &ProcessGroups)],
+    ticks_per_second: f64,
+    naming: &MetricNaming,
+) {
+    for metric in GROUP_METRICS {
+        let samples: Vec<(&OsStr, f64)> = groups
+            .iter()
+            .filter_map(|(name, group)| Some((*name, (metric.value)(group, ticks_per_second)?)))
+            .collect();
+        if samples.is_empty() {
+            continue;
+        }
+        let metric_name = naming.metric(metric.name);
+        header(out, &metric_name, metric.kind, metric.help);
+        for (name, value) in samples {
+            let _ = writeln!(
+                out,
+                "{}{{{}=\"{}\"}} {}",
+                metric_name,
+                naming.group_label,
+                escape_label(&name.to_string_lossy()),
+                value
+            );
+        }
+    }
+}
+
+fn render_coverage(out: &mut String, coverage: &Coverage, naming: &MetricNaming) {
+    let metric_name = naming.metric("scanned_processes");
+    header(
+        out,
+        &metric_name,
+        "gauge",
+        "Processes seen by the last scan, by whether they were included in a group",
+    );
+    let mut statuses = vec![
+        ("included".to_owned(), coverage.included),
+        ("kernel thread".to_owned(), coverage.kernel_threads),
+    ];
+    statuses.extend(
+        coverage
+            .skipped
+            .iter()
+            .map(|(reason, count)| (reason.to_string(), *count)),
+    );
+    for (status, count) in statuses {
+        let _ = writeln!(out, "{}{{status=\"{}\"}} {}", metric_name, status, count);
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Scans once and writes the metrics for the node_exporter textfile collector to `output`
+pub fn write_textfile(
+    options: ScanOptions,
+    output: &Path,
+    naming: &MetricNaming,
+) -> Result<(), Box<dyn Error>> {
+    naming.validate()?;
+    let ticks_per_second = procfs::ticks_per_second()? as u64;
+    let processes = GroupedProcess::scan(&options)?;
+    write_atomically(output, &render(&processes, ticks_per_second, naming))
+        .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+    Ok(())
+}
+
+/// Replaces `path` with `contents` so that readers see either the old or the new file
+///
+/// The contents are written to a temporary file in the same directory, which is then renamed
+/// over `path`. The temporary file does not end in `.prom`, so the node_exporter textfile
+/// collector ignores it.
+pub fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let file_name = path.file_name().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "Output path has no file name")
+    })?;
+    let mut temp_name = OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(".{}.tmp", process::id()));
+    let temp_path = path.with_file_name(temp_name);
+
+    let result = File::create(&temp_path).and_then(|mut file| {
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Escapes a label value as required by the exposition format
+fn escape_label(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use top_group::SkipReason;
+
+    fn naming(metric_prefix: &str, group_label: &str) -> MetricNaming {
+        MetricNaming {
+            metric_prefix: metric_prefix.to_owned(),
+            group_label: group_label.to_owned(),
+        }
+    }
+
+    #[test]
+    fn validates_naming() {
+        assert!(naming("top_group", "group").validate().is_ok());
+        assert!(naming("node:top_group", "_group2").validate().is_ok());
+        for (metric_prefix, group_label) in [
+            ("", "group"),
+            ("2top", "group"),
+            ("top-group", "group"),
+            ("top group", "group"),
+            ("top_group", ""),
+            ("top_group", "2group"),
+            ("top_group", "__group"),
+            ("top_group", "group:name"),
+            ("top_group", "grüppe"),
+        ] {
+            assert!(
+                naming(metric_prefix, group_label).validate().is_err(),
+                "{:?} {:?}",
+                metric_prefix,
+                group_label
+            );
+        }
+    }
+
+    /// An empty directory to write to, unique to `test`
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("top-group-{}-{}", test, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        dir
+    }
+
+    fn dir_entries(dir: &Path) -> Vec<OsString> {
+        let mut names: Vec<OsString> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn write_atomically_replaces_file() {
+        let dir = temp_dir("replace");
+        let path = dir.join("top_group.prom");
+        fs::write(&path, "old\n").unwrap();
+        write_atomically(&path, "new\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+        assert_eq!(dir_entries(&dir), ["top_group.prom"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn write_atomically_removes_temp_file_on_failure() {
+        let dir = temp_dir("failure");
+        // Renaming a file over a non-empty directory fails after the contents were written
+        let path = dir.join("top_group.prom");
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("keep"), "").unwrap();
+        assert!(write_atomically(&path, "new\n").is_err());
+        assert_eq!(dir_entries(&dir), ["top_group.prom"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        assert_eq!(escape_label("sshd"), "sshd");
+        assert_eq!(escape_label(r"C:\bin"), r"C:\\bin");
+        assert_eq!(escape_label(r#"say "hi""#), r#"say \"hi\""#);
+        assert_eq!(escape_label("two\nlines"), r"two\nlines");
+    }
+
+    #[test]
+    fn renders_groups() {
+        let group = ProcessGroups::default();
+        let mut out = String::new();
+        render_groups(
+            &mut out,
+            &[(OsStr::new("a\"b"), &group), (OsStr::new("sshd"), &group)],
+            100.0,
+            &naming("top_group", "app"),
+        );
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            &lines[..4],
+            [
+                "# HELP top_group_memory_bytes Resident minus shared memory of the processes in the group",
+                "# TYPE top_group_memory_bytes gauge",
+                r#"top_group_memory_bytes{app="a\"b"} 0"#,
+                r#"top_group_memory_bytes{app="sshd"} 0"#,
+            ]
+        );
+        assert!(lines.contains(&"# TYPE top_group_cpu_seconds gauge"));
+        assert!(lines.contains(&r#"top_group_cpu_seconds{app="sshd"} 0"#));
+        // Cumulative figures drop when a process exits, so none of them is a counter
+        assert!(lines
+            .iter()
+            .filter(|line| line.starts_with("# TYPE"))
+            .all(|line| line.ends_with(" gauge")));
+        // Figures that were not collected are left out rather than reported as 0
+        assert!(!out.contains("top_group_pss_bytes"));
+        assert!(!out.contains("top_group_read_bytes"));
+    }
+
+    #[test]
+    fn renders_coverage() {
+        let mut coverage = Coverage {
+            included: 412,
+            kernel_threads: 150,
+            ..Default::default()
+        };
+        coverage.skipped.insert(SkipReason::PermissionDenied, 2);
+        let mut out = String::new();
+        render_coverage(&mut out, &coverage, &naming("node_top_group", "group"));
+        assert_eq!(
+            out.lines().skip(1).collect::<Vec<_>>(),
+            [
+                "# TYPE node_top_group_scanned_processes gauge",
+                r#"node_top_group_scanned_processes{status="included"} 412"#,
+                r#"node_top_group_scanned_processes{status="kernel thread"} 150"#,
+                r#"node_top_group_scanned_processes{status="permission denied"} 2"#,
+            ]
+        );
+    }
+}
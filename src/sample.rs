@@ -0,0 +1,276 @@
+//! Repeated scans and rates computed between them
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::time::{Duration, Instant};
+
+use crate::error::Result;
+use crate::{CpuTime, GroupedProcess, IoRates, IoUsage, ProcessGroups, ScanOptions};
+
+/// Rates for a group over the interval between two samples
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GroupRates {
+    /// CPU usage in percent of one CPU, so it can exceed 100 on multi-core systems (like `top`)
+    pub cpu_percent: f64,
+
+    /// I/O per second, only for processes whose I/O could be read with [`ScanOptions::io`]
+    pub io: IoRates,
+}
+
+impl GroupRates {
+    /// Computes rates for a group from the state of its processes `elapsed` ago
+    ///
+    /// `previous` maps each PID of the previous sample to its group, and `previous_uptime` is
+    /// the time of the previous sample in clock ticks after boot. A PID counts as the same
+    /// process only if its start time is unchanged. Processes that started since the previous
+    /// sample are counted with everything they consumed since they started; other processes
+    /// missing from the previous sample, e.g. because they could not be read then, are not
+    /// counted since what they consumed in between is unknown.
+    fn between(
+        previous: &HashMap<i32, &ProcessGroups>,
+        previous_uptime: u64,
+        current: &ProcessGroups,
+        elapsed: Duration,
+        ticks_per_second: u64,
+    ) -> Self {
+        let start_time =
+            |group: &ProcessGroups, pid: &i32| group.pid_to_start_time().get(pid).copied();
+        let earlier = |pid: &i32| {
+            previous
+                .get(pid)
+                .filter(|group| start_time(group, pid) == start_time(current, pid))
+        };
+        let is_new =
+            |pid: &i32| start_time(current, pid).is_some_and(|start| start >= previous_uptime);
+
+        let cpu: CpuTime = current
+            .pid_to_cpu()
+            .iter()
+            .map(
+                |(pid, cpu)| match earlier(pid).and_then(|group| group.pid_to_cpu().get(pid)) {
+                    Some(earlier) => cpu.since(earlier),
+                    None if is_new(pid) => *cpu,
+                    None => CpuTime::default(),
+                },
+            )
+            .sum();
+        let io: IoUsage = current
+            .pid_to_io()
+            .iter()
+            .map(
+                |(pid, io)| match earlier(pid).and_then(|group| group.pid_to_io().get(pid)) {
+                    Some(earlier) => io.since(earlier),
+                    None if is_new(pid) => *io,
+                    None => IoUsage::default(),
+                },
+            )
+            .sum();
+        let seconds = elapsed.as_secs_f64();
+        let cpu_percent = if seconds > 0.0 && ticks_per_second > 0 {
+            cpu.total() as f64 / ticks_per_second as f64 / seconds * 100.0
+        } else {
+            0.0
+        };
+        GroupRates {
+            cpu_percent,
+            io: IoRates::per_second(&io, seconds),
+        }
+    }
+}
+
+/// One scan taken by a [`Sampler`]
+#[derive(Debug, Clone)]
+pub struct Sample {
+    /// The scanned processes
+    processes: GroupedProcess,
+
+    /// Time since the previous sample, `None` for the first one
+    elapsed: Option<Duration>,
+
+    /// Group name to rates since the previous sample, empty for the first sample
+    name_to_rates: HashMap<OsString, GroupRates>,
+}
+
+impl Sample {
+    /// The scanned processes
+    pub fn processes(&self) -> &GroupedProcess {
+        &self.processes
+    }
+
+    /// Time since the previous sample, `None` for the first one
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.elapsed
+    }
+
+    /// Group name to rates since the previous sample, empty for the first sample
+    pub fn name_to_rates(&self) -> &HashMap<OsString, GroupRates> {
+        &self.name_to_rates
+    }
+}
+
+/// Scans processes repeatedly, computing rates such as CPU usage and I/O between scans
+///
+/// ```no_run
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// use top_group::{Sampler, ScanOptions};
+///
+/// let mut sampler = Sampler::new(ScanOptions::default())?;
+/// sampler.sample()?;
+/// thread::sleep(Duration::from_secs(1));
+/// let sample = sampler.sample()?;
+/// for (name, rates) in sample.name_to_rates() {
+///     println!("{:?} {:.1}%", name, rates.cpu_percent);
+/// }
+/// # Ok::<(), top_group::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct Sampler {
+    options: ScanOptions,
+    ticks_per_second: u64,
+
+    /// Previous scan, with when it started and the uptime in clock ticks just before it
+    previous: Option<(Instant, u64, GroupedProcess)>,
+}
+
+impl Sampler {
+    /// Creates a sampler that scans with `options`
+    pub fn new(options: ScanOptions) -> Result<Self> {
+        let ticks_per_second = procfs::ticks_per_second()? as u64;
+        Ok(Sampler {
+            options,
+            ticks_per_second,
+            previous: None,
+        })
+    }
+
+    /// Options used for each scan
+    pub fn options(&self) -> &ScanOptions {
+        &self.options
+    }
+
+    /// Scans all processes and computes rates since the previous call
+    pub fn sample(&mut self) -> Result<Sample> {
+        let uptime = procfs::Uptime::new()?.uptime;
+        let uptime = (uptime * self.ticks_per_second as f64) as u64;
+        let processes = GroupedProcess::scan(&self.options)?;
+        let now = Instant::now();
+        let (elapsed, name_to_rates) = match &self.previous {
+            Some((then, previous_uptime, previous)) => {
+                let elapsed = now.duration_since(*then);
+                let pid_to_previous: HashMap<i32, &ProcessGroups> = previous
+                    .name_to_group()
+                    .values()
+                    .flat_map(|group| group.pid_to_usage().keys().map(move |pid| (*pid, group)))
+                    .collect();
+                let name_to_rates = processes
+                    .name_to_group()
+                    .iter()
+                    .map(|(name, group)| {
+                        let rates = GroupRates::between(
+                            &pid_to_previous,
+                            *previous_uptime,
+                            group,
+                            elapsed,
+                            self.ticks_per_second,
+                        );
+                        (name.clone(), rates)
+                    })
+                    .collect();
+                (Some(elapsed), name_to_rates)
+            }
+            None => (None, HashMap::new()),
+        };
+        self.previous = Some((now, uptime, processes.clone()));
+        Ok(Sample {
+            processes,
+            elapsed,
+            name_to_rates,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Bytes, ProcessStats};
+
+    const TICKS: u64 = 100;
+
+    /// A group of processes given as PID, start time and CPU time in ticks
+    fn group(processes: &[(i32, u64, u64)]) -> ProcessGroups {
+        let mut group = ProcessGroups::default();
+        for &(pid, start_time, ticks) in processes {
+            group.add_process(
+                pid,
+                ProcessStats {
+                    usage: Default::default(),
+                    cpu: CpuTime {
+                        user: ticks,
+                        system: 0,
+                    },
+                    start_time,
+                    io: Some(IoUsage {
+                        read_bytes: Bytes::new(ticks),
+                        ..Default::default()
+                    }),
+                    counts: Default::default(),
+                    info: None,
+                },
+            );
+        }
+        group
+    }
+
+    /// Rates of `current` one second after `previous`, taken at uptime 1000
+    fn between(previous: &ProcessGroups, current: &ProcessGroups) -> GroupRates {
+        let pid_to_previous = previous
+            .pid_to_cpu()
+            .keys()
+            .map(|pid| (*pid, previous))
+            .collect();
+        GroupRates::between(
+            &pid_to_previous,
+            1000,
+            current,
+            Duration::from_secs(1),
+            TICKS,
+        )
+    }
+
+    #[test]
+    fn same_process() {
+        let rates = between(&group(&[(1, 10, 500)]), &group(&[(1, 10, 550)]));
+        assert_eq!(rates.cpu_percent, 50.0);
+        assert_eq!(rates.io.read_bytes, 50.0);
+    }
+
+    #[test]
+    fn new_process_counts_its_lifetime() {
+        let rates = between(&group(&[]), &group(&[(2, 1050, 30)]));
+        assert_eq!(rates.cpu_percent, 30.0);
+        assert_eq!(rates.io.read_bytes, 30.0);
+    }
+
+    #[test]
+    fn missed_process_is_not_charged_its_lifetime() {
+        let rates = between(&group(&[]), &group(&[(3, 10, 5000)]));
+        assert_eq!(rates.cpu_percent, 0.0);
+        assert_eq!(rates.io.read_bytes, 0.0);
+    }
+
+    #[test]
+    fn reused_pid_is_a_new_process() {
+        // Counters that did not go backwards must not be taken for the old process
+        let rates = between(&group(&[(4, 10, 20)]), &group(&[(4, 1020, 60)]));
+        assert_eq!(rates.cpu_percent, 60.0);
+        assert_eq!(rates.io.read_bytes, 60.0);
+    }
+
+    #[test]
+    fn reused_pid_of_missed_process() {
+        let rates = between(&group(&[(5, 10, 20)]), &group(&[(5, 500, 5000)]));
+        assert_eq!(rates.cpu_percent, 0.0);
+    }
+}
@@ -0,0 +1,85 @@
+//! Prometheus exporter serving per-group metrics over HTTP
+
+use std::error::Error;
+use std::io::Cursor;
+use std::time::{Duration, Instant};
+
+use tiny_http::{Header, Method, Request, Response, Server};
+use top_group::{GroupedProcess, ScanOptions};
+
+use crate::prometheus::{self, MetricNaming};
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Rendered metrics of the last scan
+struct Exporter {
+    options: ScanOptions,
+    ticks_per_second: u64,
+    cache: Duration,
+    naming: MetricNaming,
+    cached: Option<(Instant, String)>,
+}
+
+impl Exporter {
+    /// Metrics from a new scan, or from the cache if the last scan is younger than `cache`
+    fn metrics(&mut self) -> top_group::Result<String> {
+        if let Some((scanned, body)) = &self.cached {
+            if scanned.elapsed() < self.cache {
+                return Ok(body.clone());
+            }
+        }
+        let processes = GroupedProcess::scan(&self.options)?;
+        let body = prometheus::render(&processes, self.ticks_per_second, &self.naming);
+        self.cached = Some((Instant::now(), body.clone()));
+        Ok(body)
+    }
+
+    fn respond(&mut self, request: &Request) -> Response<Cursor<Vec<u8>>> {
+        if request.method() != &Method::Get && request.method() != &Method::Head {
+            return Response::from_string("").with_status_code(405);
+        }
+        // Prometheus may add parameters to the scrape URL
+        if request.url().split('?').next() != Some("/metrics") {
+            return Response::from_string("See /metrics\n").with_status_code(404);
+        }
+        match self.metrics() {
+            Ok(body) => {
+                let header =
+                    Header::from_bytes("Content-Type", CONTENT_TYPE).expect("Invalid header");
+                Response::from_string(body).with_header(header)
+            }
+            Err(e) => {
+                Response::from_string(format!("Failed to scan: {}\n", e)).with_status_code(500)
+            }
+        }
+    }
+}
+
+/// Serves metrics on `listen` forever
+///
+/// `/metrics` rescans processes on each scrape, unless the previous scan is younger than
+/// `cache`, so that several Prometheus servers scraping the same host do not each cause a scan.
+pub fn run(
+    options: ScanOptions,
+    listen: &str,
+    cache: Duration,
+    naming: MetricNaming,
+) -> Result<(), Box<dyn Error>> {
+    naming.validate()?;
+    let mut exporter = Exporter {
+        options,
+        ticks_per_second: procfs::ticks_per_second()? as u64,
+        cache,
+        naming,
+        cached: None,
+    };
+    let server = Server::http(listen).map_err(|e| -> Box<dyn Error> { e })?;
+    eprintln!("Serving metrics on http://{}/metrics", server.server_addr());
+    for request in server.incoming_requests() {
+        let response = exporter.respond(&request);
+        if let Err(e) = request.respond(response) {
+            eprintln!("Failed to respond: {}", e);
+        }
+    }
+    Ok(())
+}
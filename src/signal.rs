@@ -0,0 +1,291 @@
+//! `signal` subcommand: sends a signal to every process of a group
+
+use std::error::Error;
+use std::ffi::OsStr;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::str::FromStr;
+
+use procfs::process::Process;
+use top_group::{GroupedProcess, ProcessInfo, ScanOptions, UserNames};
+
+use crate::detail::command;
+
+/// Signals that can be given by name, with or without the `SIG` prefix
+const SIGNALS: &[(&str, libc::c_int)] = &[
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("USR2", libc::SIGUSR2),
+    ("TERM", libc::SIGTERM),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+];
+
+/// A signal given by name, e.g. `TERM` or `SIGKILL`, or by number
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Signal(libc::c_int);
+
+impl FromStr for Signal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(number) = s.parse() {
+            return match number {
+                1..=64 => Ok(Signal(number)),
+                _ => Err(format!("Invalid signal number {}", number)),
+            };
+        }
+        let upper = s.to_ascii_uppercase();
+        let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+        SIGNALS
+            .iter()
+            .find(|(known, _)| *known == name)
+            .map(|(_, number)| Signal(*number))
+            .ok_or_else(|| {
+                let names: Vec<&str> = SIGNALS.iter().map(|(name, _)| *name).collect();
+                format!(
+                    "Unknown signal {:?}, expected a number or one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl std::fmt::Display for Signal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match SIGNALS.iter().find(|(_, number)| *number == self.0) {
+            Some((name, _)) => write!(f, "SIG{}", name),
+            None => write!(f, "signal {}", self.0),
+        }
+    }
+}
+
+/// What to signal and how careful to be about it
+pub struct Request<'a> {
+    pub group: &'a str,
+    pub signal: Signal,
+
+    /// Only list the processes that would be signalled
+    pub dry_run: bool,
+
+    /// Do not ask for confirmation
+    pub yes: bool,
+
+    pub users: UserNames,
+}
+
+/// Why a process was not signalled
+enum Failure {
+    /// The process exited, or its PID now belongs to another process
+    Gone,
+    Error(io::Error),
+}
+
+impl Request<'_> {
+    /// Scans with `options`, lists the processes of the group and signals them after
+    /// confirmation
+    ///
+    /// Our own process is never signalled. Each process is checked to still have the start
+    /// time it had when scanned, so a PID reused by another process in the meantime is
+    /// skipped.
+    pub fn run(&self, options: &ScanOptions) -> Result<(), Box<dyn Error>> {
+        let options = ScanOptions {
+            info: true,
+            ..options.clone()
+        };
+        let processes = GroupedProcess::scan(&options)?;
+        let group = processes
+            .name_to_group()
+            .get(OsStr::new(self.group))
+            .ok_or_else(|| format!("No group {:?}", self.group))?;
+        let own_pid = std::process::id() as i32;
+        let mut targets: Vec<(i32, &ProcessInfo)> = group
+            .pid_to_info()
+            .iter()
+            .filter(|(pid, _)| **pid != own_pid)
+            .map(|(pid, info)| (*pid, info))
+            .collect();
+        targets.sort_unstable_by_key(|(pid, _)| *pid);
+        if targets.is_empty() {
+            return Err(format!("No processes in group {:?} to signal", self.group).into());
+        }
+
+        println!("  {:>7} {:10} COMMAND", "PID", "USER");
+        for (pid, info) in &targets {
+            println!(
+                "  {:>7} {:10} {}",
+                pid,
+                self.users.name_or_uid(info.uid),
+                command(info)
+            );
+        }
+        let summary = format!(
+            "{} to {} processes of group {:?}",
+            self.signal,
+            targets.len(),
+            self.group
+        );
+        if self.dry_run {
+            println!("Would send {}", summary);
+            return Ok(());
+        }
+        if !self.yes && !confirm(&format!("Send {}?", summary))? {
+            return Err("Aborted".into());
+        }
+
+        let (mut sent, mut gone, mut failed) = (0, 0, 0);
+        for (pid, info) in &targets {
+            match send(*pid, info.start_time, self.signal) {
+                Ok(()) => sent += 1,
+                Err(Failure::Gone) => gone += 1,
+                Err(Failure::Error(e)) => {
+                    eprintln!("Failed to signal {}: {}", pid, e);
+                    failed += 1;
+                }
+            }
+        }
+        println!(
+            "Sent {} to {} processes, {} already gone",
+            self.signal, sent, gone
+        );
+        if failed > 0 {
+            return Err(format!("Failed to signal {} processes", failed).into());
+        }
+        Ok(())
+    }
+}
+
+/// Asks on the terminal, refusing if there is none to ask on
+fn confirm(question: &str) -> Result<bool, Box<dyn Error>> {
+    if !io::stdin().is_terminal() {
+        return Err("Not asking for confirmation without a terminal, use --yes".into());
+    }
+    eprint!("{} [y/N] ", question);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Signals `pid` if it is still the process that started at `start_time`
+///
+/// Where supported, the process is pinned with a pidfd before its start time is checked, so
+/// the signal cannot reach a process that reused the PID after the check.
+fn send(pid: i32, start_time: u64, signal: Signal) -> Result<(), Failure> {
+    let pidfd = match pidfd_open(pid) {
+        Ok(pidfd) => Some(pidfd),
+        Err(e) if e.raw_os_error() == Some(libc::ESRCH) => return Err(Failure::Gone),
+        Err(e) if e.raw_os_error() == Some(libc::ENOSYS) => None,
+        Err(e) => return Err(Failure::Error(e)),
+    };
+    let current = Process::new(pid).ok().map(|process| process.stat.starttime);
+    deliver(pid, pidfd.as_ref(), start_time, current, signal)
+}
+
+/// Signals `pid`, through `pidfd` if there is one, unless its `current` start time is not the
+/// `expected` one or it is gone
+fn deliver(
+    pid: i32,
+    pidfd: Option<&OwnedFd>,
+    expected: u64,
+    current: Option<u64>,
+    signal: Signal,
+) -> Result<(), Failure> {
+    if current != Some(expected) {
+        return Err(Failure::Gone);
+    }
+    // SAFETY: plain system calls on integers; the pidfd stays open for the call
+    let result = unsafe {
+        match pidfd {
+            Some(pidfd) => libc::syscall(
+                libc::SYS_pidfd_send_signal,
+                pidfd.as_raw_fd(),
+                signal.0,
+                std::ptr::null::<libc::siginfo_t>(),
+                0,
+            ),
+            None => libc::kill(pid, signal.0).into(),
+        }
+    };
+    if result == 0 {
+        return Ok(());
+    }
+    let e = io::Error::last_os_error();
+    match e.raw_os_error() {
+        Some(libc::ESRCH) => Err(Failure::Gone),
+        _ => Err(Failure::Error(e)),
+    }
+}
+
+fn pidfd_open(pid: i32) -> io::Result<OwnedFd> {
+    // SAFETY: pidfd_open takes a PID and flags and returns a new file descriptor
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: the descriptor was just opened and is owned by nobody else
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as RawFd) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_names() {
+        assert_eq!("TERM".parse(), Ok(Signal(libc::SIGTERM)));
+        assert_eq!("SIGTERM".parse(), Ok(Signal(libc::SIGTERM)));
+        assert_eq!("kill".parse(), Ok(Signal(libc::SIGKILL)));
+        assert_eq!("sigusr1".parse(), Ok(Signal(libc::SIGUSR1)));
+        assert!("SIG".parse::<Signal>().is_err());
+        assert!("SIGSIGTERM".parse::<Signal>().is_err());
+        assert!("WINCH".parse::<Signal>().is_err());
+        assert!("".parse::<Signal>().is_err());
+    }
+
+    #[test]
+    fn parse_numbers() {
+        assert_eq!("9".parse(), Ok(Signal(9)));
+        assert_eq!("1".parse(), Ok(Signal(1)));
+        assert_eq!("64".parse(), Ok(Signal(64)));
+        for number in ["0", "65", "-9", "99999999999"] {
+            assert!(number.parse::<Signal>().is_err(), "{:?}", number);
+        }
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Signal(libc::SIGTERM).to_string(), "SIGTERM");
+        assert_eq!(Signal(40).to_string(), "signal 40");
+    }
+
+    #[test]
+    fn skip_changed_start_time() {
+        // Would kill the test if the start time were not checked first
+        let pid = std::process::id() as i32;
+        let kill = Signal(libc::SIGKILL);
+        assert!(matches!(
+            deliver(pid, None, 100, Some(200), kill),
+            Err(Failure::Gone)
+        ));
+        assert!(matches!(
+            deliver(pid, None, 100, None, kill),
+            Err(Failure::Gone)
+        ));
+    }
+
+    #[test]
+    fn signal_same_process() {
+        // Signal 0 only checks that the process can be signalled
+        let pid = std::process::id() as i32;
+        assert!(deliver(pid, None, 100, Some(100), Signal(0)).is_ok());
+        if let Ok(pidfd) = pidfd_open(pid) {
+            assert!(deliver(pid, Some(&pidfd), 100, Some(100), Signal(0)).is_ok());
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! Proportional and unique memory accounting from `/proc/[pid]/smaps_rollup`
+
+use std::fs;
+use std::io;
+
+use crate::error::Result;
+
+/// Memory figures summed over all mappings of a process, in kB
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct SmapsTotals {
+    pub(crate) pss: u64,
+    pub(crate) uss: u64,
+    pub(crate) swap_pss: u64,
+}
+
+impl SmapsTotals {
+    /// Reads `/proc/[pid]/smaps_rollup`, or sums `/proc/[pid]/smaps` on kernels before 4.14
+    pub(crate) fn read(pid: i32) -> Result<Self> {
+        let contents = match fs::read_to_string(format!("/proc/{}/smaps_rollup", pid)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                fs::read_to_string(format!("/proc/{}/smaps", pid))?
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self::parse(&contents))
+    }
+
+    /// Sums fields over all mappings
+    ///
+    /// `smaps_rollup` has the same format as `smaps` with a single mapping, so both can be
+    /// parsed the same way. Mapping header lines never match a field name and are ignored.
+    fn parse(contents: &str) -> Self {
+        let mut totals = SmapsTotals::default();
+        for line in contents.lines() {
+            let (key, value) = match line.split_once(':') {
+                Some(field) => field,
+                None => continue,
+            };
+            let field = match key {
+                "Pss" => &mut totals.pss,
+                "Private_Clean" | "Private_Dirty" => &mut totals.uss,
+                "SwapPss" => &mut totals.swap_pss,
+                _ => continue,
+            };
+            let kb = value.trim().trim_end_matches("kB").trim();
+            *field += kb.parse::<u64>().unwrap_or(0);
+        }
+        totals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_smaps_rollup() {
+        let contents = "\
+55d1c6a4e000-7ffd3b9f8000 ---p 00000000 00:00 0                          [rollup]
+Rss:                9876 kB
+Pss:                4321 kB
+Pss_Dirty:          1200 kB
+Pss_Anon:           1100 kB
+Pss_File:           3221 kB
+Pss_Shmem:             0 kB
+Shared_Clean:       5000 kB
+Shared_Dirty:        100 kB
+Private_Clean:       700 kB
+Private_Dirty:      1300 kB
+Referenced:         9000 kB
+Anonymous:          1400 kB
+Swap:                 64 kB
+SwapPss:              48 kB
+Locked:                0 kB
+";
+        assert_eq!(
+            SmapsTotals::parse(contents),
+            SmapsTotals {
+                pss: 4321,
+                uss: 2000,
+                swap_pss: 48,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_smaps_sums_mappings() {
+        let contents = "\
+00400000-0040b000 r-xp 00000000 08:01 1048602                            /usr/bin/cat
+Size:                 44 kB
+Rss:                  40 kB
+Pss:                  10 kB
+Private_Clean:         4 kB
+Private_Dirty:         0 kB
+SwapPss:               0 kB
+VmFlags: rd ex mr mw me dw sd
+7f0d2c000000-7f0d2c021000 rw-p 00000000 00:00 0
+Size:                132 kB
+Rss:                 12 kB
+Pss:                  12 kB
+Private_Clean:         0 kB
+Private_Dirty:        12 kB
+SwapPss:               8 kB
+VmFlags: rd wr mr mw me nr sd
+7ffd3b9d7000-7ffd3b9f8000 rw-p 00000000 00:00 0                          [stack]
+Pss:                  20 kB
+Private_Clean:         1 kB
+Private_Dirty:        19 kB
+SwapPss:               2 kB
+";
+        assert_eq!(
+            SmapsTotals::parse(contents),
+            SmapsTotals {
+                pss: 42,
+                uss: 36,
+                swap_pss: 10,
+            }
+        );
+    }
+
+    #[test]
+    fn ignore_headers_with_colons() {
+        // The path of a mapping may contain anything, including a field name
+        let contents = "\
+7f0d2c000000-7f0d2c021000 rw-s 00000000 00:05 42                         /tmp/Pss: 999 kB
+Pss:                   5 kB
+";
+        assert_eq!(SmapsTotals::parse(contents).pss, 5);
+    }
+
+    #[test]
+    fn parse_empty() {
+        assert_eq!(SmapsTotals::parse(""), SmapsTotals::default());
+    }
+}
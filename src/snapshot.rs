@@ -0,0 +1,152 @@
+//! Scans saved to files, to compare them later
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::GroupedProcess;
+
+/// Version of the snapshot format written by [`Snapshot::save`]
+///
+/// Fields may be added without changing the version, since [`GroupedProcess`] leaves fields
+/// missing from older snapshots empty. The version only changes when existing fields change
+/// their meaning, and snapshots of a later version are not read.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// A scan together with when and where it was taken
+///
+/// Snapshots are stored as JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Version of the snapshot format, see [`SNAPSHOT_VERSION`]
+    pub version: u32,
+
+    /// When the scan was taken, in seconds since the Unix epoch
+    pub timestamp: u64,
+
+    /// Host name of the scanned system
+    pub hostname: String,
+
+    /// Kernel release of the scanned system, e.g. `6.1.0-13-amd64`
+    pub kernel: String,
+
+    /// The scan
+    pub processes: GroupedProcess,
+}
+
+impl Snapshot {
+    /// Records `processes`, which should have just been scanned, with the current time and
+    /// system
+    pub fn new(processes: GroupedProcess) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_secs());
+        let read = |path: &str| {
+            fs::read_to_string(path)
+                .map(|contents| contents.trim().to_owned())
+                .unwrap_or_default()
+        };
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            timestamp,
+            hostname: read("/proc/sys/kernel/hostname"),
+            kernel: read("/proc/sys/kernel/osrelease"),
+            processes,
+        }
+    }
+
+    /// Writes the snapshot to a file
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()
+    }
+
+    /// Reads a snapshot written by [`save`](Self::save), by this or an earlier version
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        let snapshot: Snapshot = serde_json::from_reader(reader)?;
+        if snapshot.version > SNAPSHOT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Snapshot version {} is newer than the supported version {}",
+                    snapshot.version, SNAPSHOT_VERSION
+                ),
+            ));
+        }
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+    #[test]
+    fn round_trip() {
+        let processes = GroupedProcess::scan(&crate::ScanOptions {
+            fds: true,
+            info: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let snapshot = Snapshot::new(processes);
+        let path =
+            std::env::temp_dir().join(format!("top-group-snapshot-{}.json", std::process::id()));
+        snapshot.save(&path).unwrap();
+        let loaded = Snapshot::load(&path);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.version, SNAPSHOT_VERSION);
+        assert_eq!(loaded.timestamp, snapshot.timestamp);
+        assert_eq!(loaded.hostname, snapshot.hostname);
+        assert_eq!(loaded.kernel, snapshot.kernel);
+        let (groups, loaded_groups) = (
+            snapshot.processes.name_to_group(),
+            loaded.processes.name_to_group(),
+        );
+        assert_eq!(loaded_groups.len(), groups.len());
+        for (name, group) in groups {
+            let loaded_group = &loaded_groups[name];
+            assert_eq!(loaded_group.pid_to_usage(), group.pid_to_usage());
+            assert_eq!(loaded_group.pid_to_cpu(), group.pid_to_cpu());
+            assert_eq!(loaded_group.pid_to_start_time(), group.pid_to_start_time());
+            assert_eq!(loaded_group.pid_to_counts(), group.pid_to_counts());
+            assert_eq!(loaded_group.pid_to_info(), group.pid_to_info());
+        }
+        assert_eq!(
+            loaded.processes.coverage().skipped,
+            snapshot.processes.coverage().skipped
+        );
+    }
+
+    #[test]
+    fn loads_minimal_snapshot() {
+        // Like a snapshot written before start times and process information were recorded
+        let snapshot = Snapshot::load(format!("{}/snapshot-v1-minimal.json", FIXTURES)).unwrap();
+        assert_eq!(snapshot.version, 1);
+        assert_eq!(snapshot.hostname, "build-01");
+        let sshd = &snapshot.processes.name_to_group()[std::ffi::OsStr::new("sshd")];
+        assert_eq!(
+            sshd.pid_to_usage()[&812].resident,
+            crate::Bytes::new(7_340_032)
+        );
+        assert_eq!(sshd.pid_to_usage()[&812].pss, None);
+        assert!(sshd.pid_to_start_time().is_empty());
+        assert!(sshd.pid_to_info().is_empty());
+        assert_eq!(snapshot.processes.coverage().included, 0);
+    }
+
+    #[test]
+    fn rejects_newer_version() {
+        let error = Snapshot::load(format!("{}/snapshot-v2.json", FIXTURES)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,233 @@
+//! Times and durations of snapshots, recorded samples and alerts, always in UTC
+
+#[cfg(feature = "history")]
+use std::convert::TryFrom;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds since the Unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs())
+}
+
+/// Parses a point in time given either as how long ago, e.g. `90m`, `2h` or `1d`, or as a UTC
+/// date and time, e.g. `2024-03-01 03:00` or `2024-03-01T03:00:00`
+#[cfg(feature = "history")]
+pub fn parse_time(s: &str) -> Result<u64, String> {
+    if let Some(ago) = parse_duration(s) {
+        return Ok(now().saturating_sub(ago));
+    }
+    let invalid = || {
+        format!(
+            "invalid time '{}', expected e.g. 2h (ago) or 2024-03-01 03:00 (UTC)",
+            s
+        )
+    };
+    let (date, time) = s.split_once([' ', 'T']).unwrap_or((s, "00:00"));
+    let number = |s: &str| s.parse::<i64>().map_err(|_| invalid());
+    let date: Vec<&str> = date.split('-').collect();
+    let time: Vec<&str> = time.split(':').collect();
+    if date.len() != 3 || !(2..=3).contains(&time.len()) {
+        return Err(invalid());
+    }
+    let (year, month, day) = (number(date[0])?, number(date[1])?, number(date[2])?);
+    let (hour, minute) = (number(time[0])?, number(time[1])?);
+    let second = time.get(2).map_or(Ok(0), |second| number(second))?;
+    if !(1970..=9999).contains(&year)
+        || !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+        || !(0..24).contains(&hour)
+        || !(0..60).contains(&minute)
+        || !(0..60).contains(&second)
+    {
+        return Err(invalid());
+    }
+    let timestamp = days_from_civil(year, month, day)
+        .checked_mul(86400)
+        .and_then(|seconds| seconds.checked_add(hour * 3600 + minute * 60 + second))
+        .ok_or_else(invalid)?;
+    u64::try_from(timestamp).map_err(|_| invalid())
+}
+
+/// Parses a duration with a unit, e.g. `30s`, `90m`, `2h` or `7d`, into seconds
+pub fn parse_duration(s: &str) -> Option<u64> {
+    let unit = match s.chars().last()? {
+        's' => 1,
+        'm' => 60,
+        'h' => 3600,
+        'd' => 86400,
+        _ => return None,
+    };
+    let value: u64 = s[..s.len() - 1].parse().ok()?;
+    value.checked_mul(unit)
+}
+
+/// Formats seconds as e.g. `2d 3h 4m 5s`, leaving out leading zero units
+#[cfg(feature = "serde")]
+pub fn format_duration(seconds: u64) -> String {
+    let units = [
+        (seconds / 86400, 'd'),
+        (seconds / 3600 % 24, 'h'),
+        (seconds / 60 % 60, 'm'),
+        (seconds % 60, 's'),
+    ];
+    let first = units.iter().position(|(value, _)| *value > 0).unwrap_or(3);
+    units[first..]
+        .iter()
+        .map(|(value, unit)| format!("{}{}", value, unit))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Formats seconds since the Unix epoch as `YYYY-MM-DD HH:MM:SS UTC`
+pub fn format_timestamp(timestamp: u64) -> String {
+    // See http://howardhinnant.github.io/date_algorithms.html for this and days_from_civil
+    let days = (timestamp / 86400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    let seconds = timestamp % 86400;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Number of days in a month of the proleptic Gregorian calendar
+#[cfg(feature = "history")]
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since the Unix epoch of a date in the proleptic Gregorian calendar
+#[cfg(feature = "history")]
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_known_timestamps() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00:00 UTC");
+        assert_eq!(format_timestamp(1_709_262_000), "2024-03-01 03:00:00 UTC");
+        assert_eq!(format_timestamp(4_107_542_399), "2100-02-28 23:59:59 UTC");
+        assert_eq!(format_timestamp(4_107_542_400), "2100-03-01 00:00:00 UTC");
+        assert_eq!(format_timestamp(253_402_300_799), "9999-12-31 23:59:59 UTC");
+    }
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse_duration("30s"), Some(30));
+        assert_eq!(parse_duration("90m"), Some(5400));
+        assert_eq!(parse_duration("2h"), Some(7200));
+        assert_eq!(parse_duration("7d"), Some(604_800));
+        assert_eq!(parse_duration("0s"), Some(0));
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("s"), None);
+        assert_eq!(parse_duration("5"), None);
+        assert_eq!(parse_duration("5x"), None);
+        assert_eq!(parse_duration("-5m"), None);
+        assert_eq!(parse_duration("1.5h"), None);
+        assert_eq!(parse_duration("18446744073709551615d"), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn format_durations() {
+        assert_eq!(format_duration(0), "0s");
+        assert_eq!(format_duration(59), "59s");
+        assert_eq!(format_duration(3600), "1h 0m 0s");
+        assert_eq!(
+            format_duration(2 * 86400 + 3 * 3600 + 4 * 60 + 5),
+            "2d 3h 4m 5s"
+        );
+    }
+
+    #[cfg(feature = "history")]
+    #[test]
+    fn parse_known_times() {
+        assert_eq!(parse_time("1970-01-01 00:00"), Ok(0));
+        assert_eq!(parse_time("2000-02-29 00:00"), Ok(951_782_400));
+        assert_eq!(parse_time("2024-03-01 03:00"), Ok(1_709_262_000));
+        assert_eq!(parse_time("2024-03-01T03:00:00"), Ok(1_709_262_000));
+        assert_eq!(parse_time("2024-03-01"), Ok(1_709_251_200));
+        assert_eq!(parse_time("2100-02-28 23:59:59"), Ok(4_107_542_399));
+        assert_eq!(parse_time("9999-12-31 23:59:59"), Ok(253_402_300_799));
+    }
+
+    #[cfg(feature = "history")]
+    #[test]
+    fn parse_relative_time() {
+        let before = now();
+        let parsed = parse_time("2h").unwrap();
+        assert!(parsed + 7200 >= before && parsed + 7200 <= now());
+    }
+
+    #[cfg(feature = "history")]
+    #[test]
+    fn reject_invalid_times() {
+        for time in [
+            "1969-12-31 23:59",
+            "2100-02-29 00:00",
+            "2023-02-29 00:00",
+            "2024-04-31 00:00",
+            "2024-13-01 00:00",
+            "2024-00-01 00:00",
+            "2024-03-01 24:00",
+            "2024-03-01 03:60",
+            "2024-03-01 03:00:60",
+            "2024-03-01 03",
+            "2024-03",
+            "9999999999999999-01-01",
+            "-9999999999999999-01-01",
+            "10000-01-01 00:00",
+            "yesterday",
+            "",
+        ] {
+            assert!(parse_time(time).is_err(), "{:?} was accepted", time);
+        }
+    }
+
+    #[cfg(feature = "history")]
+    #[test]
+    fn round_trip() {
+        let mut timestamp = 0;
+        while timestamp < 253_402_300_799 {
+            let formatted = format_timestamp(timestamp);
+            let parsed = parse_time(formatted.trim_end_matches(" UTC")).unwrap();
+            assert_eq!(parsed, timestamp, "{}", formatted);
+            // A prime step, to hit all days of the month, leap days and times of day
+            timestamp += 7_919_993;
+        }
+    }
+}
@@ -0,0 +1,244 @@
+//! Parent/child relationships between processes
+
+use std::collections::{HashMap, HashSet};
+
+use procfs::process::Process;
+
+/// Ancestor level that processes are attributed to by [`GroupBy::Ancestor`](crate::GroupBy)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TreeRollup {
+    /// PID whose descendants are grouped; other processes have no key
+    pub root: i32,
+
+    /// How many levels below `root` the grouping ancestor is
+    ///
+    /// With a depth of 1, every child of `root` forms a group with all of its descendants.
+    /// Processes above this level form a group of their own.
+    pub depth: usize,
+}
+
+impl Default for TreeRollup {
+    fn default() -> Self {
+        TreeRollup { root: 1, depth: 1 }
+    }
+}
+
+/// Tree of the processes in a group, rooted at the ancestor they were attributed to
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProcessTree {
+    /// Ancestor of all processes in the tree
+    root: i32,
+
+    /// PID to child PIDs mapping, children sorted by PID
+    children: HashMap<i32, Vec<i32>>,
+
+    /// PID to command name mapping
+    names: HashMap<i32, String>,
+}
+
+impl ProcessTree {
+    /// Ancestor of all processes in the tree
+    pub fn root(&self) -> i32 {
+        self.root
+    }
+
+    /// Children of a process within the tree
+    pub fn children(&self, pid: i32) -> &[i32] {
+        self.children.get(&pid).map_or(&[], Vec::as_slice)
+    }
+
+    /// Command name of a process
+    pub fn name(&self, pid: i32) -> Option<&str> {
+        self.names.get(&pid).map(String::as_str)
+    }
+
+    /// Depth-first traversal yielding `(depth, pid)`, starting with `(0, root)`
+    pub fn walk(&self) -> Vec<(usize, i32)> {
+        let mut walked = Vec::new();
+        let mut stack = vec![(0, self.root)];
+        while let Some((depth, pid)) = stack.pop() {
+            walked.push((depth, pid));
+            stack.extend(
+                self.children(pid)
+                    .iter()
+                    .rev()
+                    .map(|&child| (depth + 1, child)),
+            );
+        }
+        walked
+    }
+}
+
+/// Parent and name of every process on the system, used to find ancestors
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ProcessTable {
+    pid_to_ppid: HashMap<i32, i32>,
+    pid_to_comm: HashMap<i32, String>,
+}
+
+impl ProcessTable {
+    pub(crate) fn new(procs: &[Process]) -> Self {
+        ProcessTable {
+            pid_to_ppid: procs.iter().map(|p| (p.pid(), p.stat.ppid)).collect(),
+            pid_to_comm: procs
+                .iter()
+                .map(|p| (p.pid(), p.stat.comm.clone()))
+                .collect(),
+        }
+    }
+
+    /// Ancestor that `pid` is attributed to, or `None` if it does not descend from the root
+    pub(crate) fn ancestor(&self, pid: i32, rollup: &TreeRollup) -> Option<i32> {
+        let mut path = vec![pid];
+        let mut current = pid;
+        while current != rollup.root {
+            // Bounding the walk by the table size protects against cycles from PID reuse
+            if path.len() > self.pid_to_ppid.len() {
+                return None;
+            }
+            current = *self.pid_to_ppid.get(&current)?;
+            path.push(current);
+        }
+        path.reverse();
+        Some(*path.get(rollup.depth).unwrap_or(&pid))
+    }
+
+    /// Group name for an ancestor, e.g. `bash [1234]`
+    pub(crate) fn ancestor_name(&self, ancestor: i32) -> String {
+        let comm = self.pid_to_comm.get(&ancestor).map_or("?", String::as_str);
+        format!("{} [{}]", comm, ancestor)
+    }
+
+    /// Tree of `members` rooted at `root`
+    ///
+    /// Members whose parent is not a member are attached directly to the root, and so are
+    /// members whose parents lead back to themselves, which PID reuse can cause.
+    pub(crate) fn subtree(&self, root: i32, members: impl Iterator<Item = i32>) -> ProcessTree {
+        let members: HashSet<i32> = members.collect();
+        let mut children: HashMap<i32, Vec<i32>> = HashMap::new();
+        for &pid in &members {
+            if pid == root {
+                continue;
+            }
+            let parent = match self.pid_to_ppid.get(&pid) {
+                Some(ppid) if members.contains(ppid) && !self.in_cycle(pid, &members) => *ppid,
+                _ => root,
+            };
+            children.entry(parent).or_default().push(pid);
+        }
+        for pids in children.values_mut() {
+            pids.sort_unstable();
+        }
+        let names = members
+            .iter()
+            .chain(Some(&root))
+            .filter_map(|pid| Some((*pid, self.pid_to_comm.get(pid)?.clone())))
+            .collect();
+        ProcessTree {
+            root,
+            children,
+            names,
+        }
+    }
+
+    /// Whether following parents from `pid` through `members` leads back to `pid`
+    fn in_cycle(&self, pid: i32, members: &HashSet<i32>) -> bool {
+        let mut current = pid;
+        for _ in 0..members.len() {
+            current = match self.pid_to_ppid.get(&current) {
+                Some(ppid) if members.contains(ppid) => *ppid,
+                _ => return false,
+            };
+            if current == pid {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Table of `(pid, ppid)` pairs, each process named `p<pid>`
+    fn table(processes: &[(i32, i32)]) -> ProcessTable {
+        ProcessTable {
+            pid_to_ppid: processes.iter().copied().collect(),
+            pid_to_comm: processes
+                .iter()
+                .map(|(pid, _)| (*pid, format!("p{}", pid)))
+                .collect(),
+        }
+    }
+
+    /// 1 -> 10 -> 100 -> 1000, and 1 -> 20
+    fn chain() -> ProcessTable {
+        table(&[(1, 0), (10, 1), (100, 10), (1000, 100), (20, 1)])
+    }
+
+    #[test]
+    fn ancestor_depths() {
+        let table = chain();
+        let rollup = |depth| TreeRollup { root: 1, depth };
+        assert_eq!(table.ancestor(1000, &rollup(0)), Some(1));
+        assert_eq!(table.ancestor(1000, &rollup(1)), Some(10));
+        assert_eq!(table.ancestor(1000, &rollup(2)), Some(100));
+        assert_eq!(table.ancestor(1000, &rollup(3)), Some(1000));
+        assert_eq!(table.ancestor(20, &rollup(1)), Some(20));
+    }
+
+    #[test]
+    fn ancestor_above_depth() {
+        let table = chain();
+        let rollup = TreeRollup { root: 1, depth: 2 };
+        assert_eq!(table.ancestor(1, &rollup), Some(1));
+        assert_eq!(table.ancestor(10, &rollup), Some(10));
+        assert_eq!(table.ancestor(20, &rollup), Some(20));
+    }
+
+    #[test]
+    fn ancestor_outside_root() {
+        let table = chain();
+        let rollup = TreeRollup { root: 10, depth: 1 };
+        assert_eq!(table.ancestor(1000, &rollup), Some(100));
+        assert_eq!(table.ancestor(20, &rollup), None);
+        assert_eq!(table.ancestor(1, &rollup), None);
+        assert_eq!(table.ancestor(42, &rollup), None, "unknown PID");
+    }
+
+    #[test]
+    fn ancestor_cycle() {
+        let table = table(&[(1, 0), (5, 6), (6, 5), (7, 6)]);
+        let rollup = TreeRollup::default();
+        assert_eq!(table.ancestor(5, &rollup), None);
+        assert_eq!(table.ancestor(7, &rollup), None);
+    }
+
+    #[test]
+    fn subtree_nests_members() {
+        let tree = chain().subtree(10, [10, 100, 1000].iter().copied());
+        assert_eq!(tree.root(), 10);
+        assert_eq!(tree.walk(), vec![(0, 10), (1, 100), (2, 1000)]);
+        assert_eq!(tree.name(1000), Some("p1000"));
+    }
+
+    #[test]
+    fn subtree_attaches_orphans_to_root() {
+        // 100 is not a member, so its child 1000 hangs directly below the root
+        let tree = chain().subtree(1, [1, 10, 20, 1000].iter().copied());
+        assert_eq!(tree.children(1), &[10, 20, 1000]);
+        assert_eq!(tree.children(10), &[] as &[i32]);
+    }
+
+    #[test]
+    fn subtree_breaks_cycles() {
+        let table = table(&[(1, 0), (5, 6), (6, 5), (7, 6)]);
+        let tree = table.subtree(1, [5, 6, 7].iter().copied());
+        assert_eq!(tree.children(1), &[5, 6]);
+        assert_eq!(tree.children(6), &[7]);
+        let walked: HashSet<i32> = tree.walk().into_iter().map(|(_, pid)| pid).collect();
+        assert_eq!(walked, [1, 5, 6, 7].iter().copied().collect());
+    }
+}
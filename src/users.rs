@@ -0,0 +1,130 @@
+//! Resolution of UIDs to user names
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Default location of the passwd database
+pub const DEFAULT_PASSWD_PATH: &str = "/etc/passwd";
+
+/// Which UID of a process to use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UidKind {
+    /// Real UID (the user who started the process)
+    #[default]
+    Real,
+
+    /// Effective UID (the user whose permissions the process has)
+    Effective,
+}
+
+/// Mapping from UID to user name, as read from a passwd(5) file
+#[derive(Debug, Clone, Default)]
+pub struct UserNames {
+    uid_to_name: HashMap<u32, String>,
+}
+
+impl UserNames {
+    /// Reads user names from a passwd(5) formatted file
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Ok(Self::parse(&contents))
+    }
+
+    /// Parses passwd(5) formatted text
+    ///
+    /// Malformed lines are ignored. If a UID appears more than once, the first entry wins.
+    pub fn parse(contents: &str) -> Self {
+        let mut uid_to_name = HashMap::new();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split(':');
+            let name = fields.next();
+            let uid = fields.nth(1).and_then(|uid| uid.parse::<u32>().ok());
+            if let (Some(name), Some(uid)) = (name, uid) {
+                uid_to_name.entry(uid).or_insert_with(|| name.to_owned());
+            }
+        }
+        UserNames { uid_to_name }
+    }
+
+    /// User name for a UID
+    pub fn name(&self, uid: u32) -> Option<&str> {
+        self.uid_to_name.get(&uid).map(String::as_str)
+    }
+
+    /// User name for a UID, or the numeric UID if it has no name
+    pub fn name_or_uid(&self, uid: u32) -> String {
+        match self.name(uid) {
+            Some(name) => name.to_owned(),
+            None => uid.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_entries() {
+        let names = UserNames::parse(
+            "root:x:0:0:root:/root:/bin/bash\n\
+             daemon:x:1:1:daemon:/usr/sbin:/usr/sbin/nologin\n\
+             alice:x:1000:1000:Alice,,,:/home/alice:/bin/bash\n",
+        );
+        assert_eq!(names.name(0), Some("root"));
+        assert_eq!(names.name(1), Some("daemon"));
+        assert_eq!(names.name(1000), Some("alice"));
+        assert_eq!(names.name(1001), None);
+        assert_eq!(names.name_or_uid(1000), "alice");
+        assert_eq!(names.name_or_uid(1001), "1001");
+    }
+
+    #[test]
+    fn skip_comments_and_blank_lines() {
+        let names = UserNames::parse(
+            "# comment:x:5:5::/:/bin/sh\n\
+             \n   \n\
+             \t# indented:x:6:6::/:/bin/sh\n\
+             bob:x:7:7::/:/bin/sh\n",
+        );
+        assert_eq!(names.name(5), None);
+        assert_eq!(names.name(6), None);
+        assert_eq!(names.name(7), Some("bob"));
+    }
+
+    #[test]
+    fn skip_malformed_lines() {
+        let names = UserNames::parse(
+            "no-colons\n\
+             short:x\n\
+             negative:x:-1:0::/:/bin/sh\n\
+             huge:x:4294967296:0::/:/bin/sh\n\
+             word:x:abc:0::/:/bin/sh\n\
+             +nis\n\
+             carol:x:8:8::/:/bin/sh\n",
+        );
+        assert_eq!(names.name(8), Some("carol"));
+        assert_eq!(names.uid_to_name.len(), 1);
+    }
+
+    #[test]
+    fn first_duplicate_wins() {
+        let names = UserNames::parse(
+            "root:x:0:0::/root:/bin/sh\n\
+             toor:x:0:0::/root:/bin/sh\n",
+        );
+        assert_eq!(names.name(0), Some("root"));
+    }
+
+    #[test]
+    fn missing_file() {
+        let e = UserNames::from_file("/nonexistent/passwd").unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,108 @@
+//! Periodic reports with per-group changes between samples
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io::{self, IsTerminal};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use top_group::Sampler;
+
+use crate::filter::Filter;
+use crate::{SortKey, Units};
+
+const BOLD_RED: &str = "\x1b[1;31m";
+const RESET: &str = "\x1b[0m";
+
+/// Rescans every `interval` forever, printing the value of `sort` for each group matching
+/// `filter` together with its change since the previous sample and since the first one
+pub fn run(
+    mut sampler: Sampler,
+    sort: SortKey,
+    filter: &Filter,
+    units: Units,
+    interval: Duration,
+) -> top_group::Result<()> {
+    let color = io::stdout().is_terminal();
+    let start = Instant::now();
+    let mut first: Option<HashMap<OsString, u64>> = None;
+    let mut previous: Option<HashMap<OsString, u64>> = None;
+    loop {
+        let sample = sampler.sample()?;
+        let current: HashMap<OsString, u64> = sample
+            .processes()
+            .name_to_group()
+            .iter()
+            .filter(|(name, group)| filter.matches(name, group))
+            .map(|(name, group)| (name.clone(), sort.group_value(group)))
+            .collect();
+        let first = first.get_or_insert_with(|| current.clone());
+
+        let mut names: Vec<&OsString> = current.keys().collect();
+        if sort == SortKey::Name {
+            names.sort_unstable();
+        } else {
+            names.sort_unstable_by_key(|name| (current[*name], *name));
+        }
+
+        println!(
+            "--- {:.0}s since start, {} groups ---",
+            start.elapsed().as_secs_f64(),
+            current.len()
+        );
+        println!(
+            "  {:30} {:>10} {:>11} {:>11}",
+            "NAME", "VALUE", "SINCE PREV", "SINCE START"
+        );
+        for name in names {
+            let value = current[name];
+            // Nothing to compare with on the first sample, rather than every group being new
+            let since_previous = match &previous {
+                Some(previous) => previous
+                    .get(name)
+                    .map_or_else(|| "new".to_owned(), |prev| delta(sort, units, value, *prev)),
+                None => "-".to_owned(),
+            };
+            let since_first = first.get(name).map_or_else(
+                || "new".to_owned(),
+                |first| delta(sort, units, value, *first),
+            );
+            let prev = previous.as_ref().and_then(|previous| previous.get(name));
+            let grew = prev.is_some_and(|prev| value > *prev);
+            let line = format!(
+                "{} {:30} {:>10} {:>11} {:>11}",
+                if grew { '+' } else { ' ' },
+                name.to_string_lossy(),
+                sort.format(value, units),
+                since_previous,
+                since_first,
+            );
+            if grew && color {
+                println!("{}{}{}", BOLD_RED, line, RESET);
+            } else {
+                println!("{}", line);
+            }
+        }
+        let gone = previous
+            .iter()
+            .flat_map(HashMap::keys)
+            .filter(|name| !current.contains_key(*name))
+            .count();
+        if gone > 0 {
+            println!("{} groups disappeared", gone);
+        }
+        println!();
+
+        previous = Some(current);
+        thread::sleep(interval);
+    }
+}
+
+/// Signed difference formatted like the value itself, e.g. `+1.2MB`
+fn delta(sort: SortKey, units: Units, value: u64, earlier: u64) -> String {
+    if value >= earlier {
+        format!("+{}", sort.format(value - earlier, units))
+    } else {
+        format!("-{}", sort.format(earlier - value, units))
+    }
+}